@@ -0,0 +1,118 @@
+use automerge::{ActorId, ObjId};
+use serde::{de, Deserialize, Serialize};
+use std::marker::PhantomData;
+
+/// Newtype struct name that the [`crate::Deserializer`] recognizes to pass along the id of the
+/// operation that wrote a value.
+pub(crate) const ATTRIBUTED: &str = "$serde_automerge::Attributed";
+
+/// A value together with the id of the operation that wrote it.
+///
+/// When read through the [`crate::Deserializer`] `op` is the id that the document returns next to
+/// every value, from which the actor that wrote the value can be read without walking the
+/// history. For objects this is the operation that created the object. `op` is [`None`] for the
+/// root, for values that don't exist and when deserializing from other formats.
+///
+/// Serializes as just the value.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Attributed<T> {
+    pub value: T,
+    pub op: Option<ObjId>,
+}
+
+impl<T> Attributed<T> {
+    pub fn new(value: T) -> Self {
+        Self { value, op: None }
+    }
+    /// The actor that wrote the value.
+    pub fn actor(&self) -> Option<&ActorId> {
+        match &self.op {
+            Some(ObjId::Id(_, actor, _)) => Some(actor),
+            _ => None,
+        }
+    }
+    /// The counter of the operation that wrote the value, which increases with every operation
+    /// an actor makes.
+    pub fn counter(&self) -> Option<u64> {
+        match &self.op {
+            Some(ObjId::Id(counter, _, _)) => Some(*counter),
+            _ => None,
+        }
+    }
+    pub fn into_inner(self) -> T {
+        self.value
+    }
+}
+
+impl<T> std::ops::Deref for Attributed<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.value
+    }
+}
+
+impl<T: Serialize> Serialize for Attributed<T> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.value.serialize(serializer)
+    }
+}
+
+/// The id of an operation, as encoded by [`ObjId::to_bytes()`].
+struct Op(ObjId);
+
+impl<'de> Deserialize<'de> for Op {
+    fn deserialize<D: de::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct Visitor;
+
+        impl<'de> de::Visitor<'de> for Visitor {
+            type Value = Op;
+
+            fn expecting(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                formatter.write_str("an encoded operation id")
+            }
+
+            fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<Self::Value, E> {
+                ObjId::try_from(v).map(Op).map_err(E::custom)
+            }
+        }
+
+        deserializer.deserialize_bytes(Visitor)
+    }
+}
+
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for Attributed<T> {
+    fn deserialize<D: de::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct Visitor<T>(PhantomData<T>);
+
+        impl<'de, T: Deserialize<'de>> de::Visitor<'de> for Visitor<T> {
+            type Value = Attributed<T>;
+
+            fn expecting(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                formatter.write_str("a value")
+            }
+
+            fn visit_newtype_struct<D: de::Deserializer<'de>>(
+                self,
+                deserializer: D,
+            ) -> Result<Self::Value, D::Error> {
+                T::deserialize(deserializer).map(Attributed::new)
+            }
+
+            fn visit_seq<A: de::SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+                let Op(op) = seq
+                    .next_element()?
+                    .ok_or_else(|| de::Error::invalid_length(0, &self))?;
+                let value = seq
+                    .next_element()?
+                    .ok_or_else(|| de::Error::invalid_length(1, &self))?;
+                Ok(Attributed {
+                    value,
+                    op: Some(op),
+                })
+            }
+        }
+
+        deserializer.deserialize_newtype_struct(ATTRIBUTED, Visitor(PhantomData))
+    }
+}
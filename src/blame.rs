@@ -0,0 +1,94 @@
+use crate::{
+    diff::get_path,
+    history::{advance, ChangeMeta},
+    patch::{overlaps, targets},
+    AutomergeSerdeError, Deserializer,
+};
+use automerge::{AutoCommit, Automerge, ChangeHash, ObjId, Prop};
+use serde::de::DeserializeOwned;
+
+/// The operation and change that last wrote a value.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Blame {
+    /// The id of the operation that wrote the value, which is also the id of the object for
+    /// objects.
+    pub op: ObjId,
+    pub change: ChangeMeta,
+}
+
+/// A change that touched a value, with the value before and after the change.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldEdit<T> {
+    pub change: ChangeMeta,
+    /// [`None`] if the value did not exist before the change.
+    pub before: Option<T>,
+    /// [`None`] if the change removed the value.
+    pub after: Option<T>,
+}
+
+/// Find which change last wrote the value at `path` below the root of `doc`.
+///
+/// For objects this is the change that created the object, use [`field_history()`] to find the changes
+/// made inside of it. Returns [`None`] if there is no value at `path`.
+pub fn blame(doc: &Automerge, path: &[Prop]) -> Result<Option<Blame>, AutomergeSerdeError> {
+    let Some((_, op)) = get_path(doc, path, None)? else {
+        return Ok(None);
+    };
+    let ObjId::Id(counter, actor, _) = &op else {
+        return Ok(None);
+    };
+    let mut heads = Vec::new();
+    for change in doc.get_changes(&[]) {
+        advance(&mut heads, change);
+        let start = change.start_op().get();
+        if change.actor_id() == actor && (start..=change.max_op()).contains(counter) {
+            return Ok(Some(Blame {
+                op: op.clone(),
+                change: ChangeMeta::new(change, &heads),
+            }));
+        }
+    }
+    Ok(None)
+}
+
+fn read<T: DeserializeOwned>(
+    doc: &Automerge,
+    path: &[Prop],
+    heads: &[ChangeHash],
+) -> Result<Option<T>, AutomergeSerdeError> {
+    get_path(doc, path, Some(heads))?
+        .map(|node| T::deserialize(Deserializer::new(doc, Some(node)).with_heads(heads)))
+        .transpose()
+        .map_err(Into::into)
+}
+
+/// List every change that touched the value at `path` below the root of `doc`, or anything inside
+/// of it, with the value of type `T` before and after each change.
+///
+/// The changes are replayed once into a scratch document, which yields the patches of each change
+/// on its own, so only the values before and after the changes that touched `path` are read.
+pub fn field_history<T: DeserializeOwned>(
+    doc: &Automerge,
+    path: &[Prop],
+) -> Result<Vec<FieldEdit<T>>, AutomergeSerdeError> {
+    let mut replay = AutoCommit::new_with_encoding(doc.text_encoding());
+    replay.update_diff_cursor();
+    let mut edits = Vec::new();
+    let mut heads = Vec::new();
+    for change in doc.get_changes(&[]) {
+        let before = heads.clone();
+        advance(&mut heads, change);
+        replay.apply_changes([change.clone()])?;
+        let touched = targets(replay.diff_incremental())
+            .iter()
+            .any(|target| overlaps(target, path));
+        if touched {
+            edits.push(FieldEdit {
+                change: ChangeMeta::new(change, &heads),
+                before: read(doc, path, &before)?,
+                after: read(doc, path, &heads)?,
+            });
+        }
+    }
+    Ok(edits)
+}
@@ -0,0 +1,81 @@
+use crate::{AutomergeSerdeError, TypedDoc};
+use automerge::ChangeHash;
+use serde::{de::DeserializeOwned, Serialize};
+use std::collections::BTreeMap;
+
+/// A main [`TypedDoc`] with named branches that are forked from it and merged back into it.
+///
+/// Branches are edited like any other [`TypedDoc`]. Changes made to the main document after a
+/// branch was created are not visible on the branch until it is merged back.
+pub struct Branches<T> {
+    main: TypedDoc<T>,
+    branches: BTreeMap<String, TypedDoc<T>>,
+}
+
+impl<T: Serialize + DeserializeOwned> Branches<T> {
+    pub fn new(main: TypedDoc<T>) -> Self {
+        Self {
+            main,
+            branches: BTreeMap::new(),
+        }
+    }
+    pub fn main(&self) -> &TypedDoc<T> {
+        &self.main
+    }
+    pub fn main_mut(&mut self) -> &mut TypedDoc<T> {
+        &mut self.main
+    }
+    pub fn into_main(self) -> TypedDoc<T> {
+        self.main
+    }
+
+    /// Create a branch from the main document at `heads`, or at its current heads if [`None`].
+    pub fn fork(
+        &mut self,
+        name: &str,
+        heads: Option<&[ChangeHash]>,
+    ) -> Result<&mut TypedDoc<T>, AutomergeSerdeError> {
+        if self.branches.contains_key(name) {
+            return Err(AutomergeSerdeError::BranchExists(name.to_owned()));
+        }
+        let branch = match heads {
+            Some(heads) => self.main.fork_at(heads)?,
+            None => self.main.fork(),
+        };
+        Ok(self.branches.entry(name.to_owned()).or_insert(branch))
+    }
+    pub fn branch(&self, name: &str) -> Option<&TypedDoc<T>> {
+        self.branches.get(name)
+    }
+    pub fn branch_mut(&mut self, name: &str) -> Option<&mut TypedDoc<T>> {
+        self.branches.get_mut(name)
+    }
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.branches.keys().map(String::as_str)
+    }
+    /// Remove a branch without merging it.
+    pub fn discard(&mut self, name: &str) -> Option<TypedDoc<T>> {
+        self.branches.remove(name)
+    }
+
+    fn get_mut(&mut self, name: &str) -> Result<&mut TypedDoc<T>, AutomergeSerdeError> {
+        self.branches
+            .get_mut(name)
+            .ok_or_else(|| AutomergeSerdeError::BranchNotFound(name.to_owned()))
+    }
+    /// The value that the main document would have after merging the branch, without changing
+    /// either of them.
+    pub fn preview(&mut self, name: &str) -> Result<T, AutomergeSerdeError> {
+        let mut merged = self.main.fork();
+        merged.merge(self.get_mut(name)?)?;
+        merged.read()
+    }
+    /// Merge a branch into the main document and remove it.
+    pub fn merge(&mut self, name: &str) -> Result<Vec<ChangeHash>, AutomergeSerdeError> {
+        let mut branch = self
+            .branches
+            .remove(name)
+            .ok_or_else(|| AutomergeSerdeError::BranchNotFound(name.to_owned()))?;
+        self.main.merge(&mut branch)
+    }
+}
@@ -0,0 +1,36 @@
+use super::{Deserializer as ValueDeserializer, Error};
+use automerge::ReadDoc;
+use serde::de::{self, value::BytesDeserializer};
+
+/// Yields the id of the operation that wrote a value followed by the value itself, which is what
+/// [`crate::Attributed`] expects.
+pub(crate) struct AttributedDeserializer<'a, Rx: ReadDoc> {
+    op: Option<Vec<u8>>,
+    value: Option<ValueDeserializer<'a, Rx>>,
+}
+
+impl<'a, Rx: ReadDoc> AttributedDeserializer<'a, Rx> {
+    pub(crate) fn new(op: Vec<u8>, value: ValueDeserializer<'a, Rx>) -> Self {
+        Self {
+            op: Some(op),
+            value: Some(value),
+        }
+    }
+}
+
+impl<'de, Rx: ReadDoc> de::SeqAccess<'de> for AttributedDeserializer<'_, Rx> {
+    type Error = Error;
+
+    fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>, Self::Error>
+    where
+        T: de::DeserializeSeed<'de>,
+    {
+        if let Some(op) = self.op.take() {
+            seed.deserialize(BytesDeserializer::new(&op)).map(Some)
+        } else if let Some(value) = self.value.take() {
+            seed.deserialize(value).map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+}
@@ -0,0 +1,97 @@
+use super::Error;
+use serde::{
+    de::{self, IntoDeserializer},
+    forward_to_deserialize_any,
+};
+
+/// Deserializes map keys that were written by [`crate::ser::KeySerializer`], parsing them back
+/// into numbers, booleans or chars when the target type asks for one.
+pub struct KeyDeserializer<'a> {
+    key: &'a str,
+}
+
+impl<'a> KeyDeserializer<'a> {
+    pub fn new(key: &'a str) -> Self {
+        Self { key }
+    }
+}
+
+macro_rules! deserialize_parse {
+    ($method:ident, $visit:ident) => {
+        fn $method<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+        where
+            V: de::Visitor<'de>,
+        {
+            match self.key.parse() {
+                Ok(v) => visitor.$visit(v),
+                Err(_) => self.deserialize_any(visitor),
+            }
+        }
+    };
+}
+
+impl<'de> de::Deserializer<'de> for KeyDeserializer<'_> {
+    type Error = Error;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        visitor.visit_str(self.key)
+    }
+
+    deserialize_parse!(deserialize_bool, visit_bool);
+
+    deserialize_parse!(deserialize_i8, visit_i8);
+    deserialize_parse!(deserialize_i16, visit_i16);
+    deserialize_parse!(deserialize_i32, visit_i32);
+    deserialize_parse!(deserialize_i64, visit_i64);
+    deserialize_parse!(deserialize_i128, visit_i128);
+
+    deserialize_parse!(deserialize_u8, visit_u8);
+    deserialize_parse!(deserialize_u16, visit_u16);
+    deserialize_parse!(deserialize_u32, visit_u32);
+    deserialize_parse!(deserialize_u64, visit_u64);
+    deserialize_parse!(deserialize_u128, visit_u128);
+
+    deserialize_parse!(deserialize_f32, visit_f32);
+    deserialize_parse!(deserialize_f64, visit_f64);
+
+    deserialize_parse!(deserialize_char, visit_char);
+
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        IntoDeserializer::<Error>::into_deserializer(self.key)
+            .deserialize_enum(name, variants, visitor)
+    }
+
+    forward_to_deserialize_any! {
+        str string bytes byte_buf unit unit_struct seq tuple
+        tuple_struct map struct identifier ignored_any
+    }
+}
@@ -0,0 +1,211 @@
+use super::{Deserializer as ValueDeserializer, Error};
+use crate::diff::FieldValue;
+use automerge::{ChangeHash, ObjId, Prop, ReadDoc, ScalarValue, Value};
+use serde::{de, Deserialize};
+use std::{cell::RefCell, slice};
+
+/// The paths below the repaired object with the values found there that did not fit the type.
+pub(crate) type Found = RefCell<Vec<(Vec<Prop>, Option<FieldValue>)>>;
+
+/// Where a lenient [`Deserializer`][ValueDeserializer] is in the document, and where it records
+/// the values that it had to convert or replace.
+#[derive(Clone)]
+pub(crate) struct Lenient<'a> {
+    path: Vec<Prop>,
+    found: &'a Found,
+}
+
+impl<'a> Lenient<'a> {
+    pub(crate) fn new(found: &'a Found) -> Self {
+        Self {
+            path: Vec::new(),
+            found,
+        }
+    }
+    pub(crate) fn child(&self, prop: Prop) -> Self {
+        let mut path = self.path.clone();
+        path.push(prop);
+        Self {
+            path,
+            found: self.found,
+        }
+    }
+    /// Record that `value` did not fit the type.
+    pub(crate) fn record<Rx: ReadDoc>(
+        &self,
+        doc: &Rx,
+        value: &Option<(Value<'_>, ObjId)>,
+        heads: Option<&[ChangeHash]>,
+    ) -> Result<(), Error> {
+        let mut deserializer = ValueDeserializer::new(doc, value.clone());
+        deserializer.heads = heads;
+        let value = value
+            .is_some()
+            .then(|| FieldValue::deserialize(deserializer))
+            .transpose()?;
+        self.found.borrow_mut().push((self.path.clone(), value));
+        Ok(())
+    }
+}
+
+/// Scalars that a lenient [`Deserializer`][ValueDeserializer] converts other scalars into.
+pub(crate) trait Coerce: Sized + Default {
+    /// The value of `s` as `Self`, and whether it had to be converted.
+    fn coerce(s: &ScalarValue) -> Option<(Self, bool)>;
+}
+
+macro_rules! impl_coerce_int {
+    ($($type:ty),*) => {
+        $(impl Coerce for $type {
+            fn coerce(s: &ScalarValue) -> Option<(Self, bool)> {
+                match s {
+                    ScalarValue::Int(v) | ScalarValue::Timestamp(v) => {
+                        Self::try_from(*v).ok().map(|v| (v, false))
+                    }
+                    ScalarValue::Counter(v) => Self::try_from(i64::from(v)).ok().map(|v| (v, false)),
+                    ScalarValue::Uint(v) => Self::try_from(*v).ok().map(|v| (v, false)),
+                    ScalarValue::F64(v) if v.fract() == 0.0 => {
+                        Self::try_from(*v as i64).ok().map(|v| (v, true))
+                    }
+                    ScalarValue::Str(v) => v.trim().parse().ok().map(|v| (v, true)),
+                    ScalarValue::Boolean(v) => Some((Self::from(*v), true)),
+                    _ => None,
+                }
+            }
+        })*
+    };
+}
+
+impl_coerce_int!(i8, i16, i32, i64, u8, u16, u32, u64);
+
+macro_rules! impl_coerce_float {
+    ($($type:ty),*) => {
+        $(impl Coerce for $type {
+            fn coerce(s: &ScalarValue) -> Option<(Self, bool)> {
+                match s {
+                    ScalarValue::F64(v) => Some((*v as $type, false)),
+                    ScalarValue::Int(v) | ScalarValue::Timestamp(v) => Some((*v as $type, false)),
+                    ScalarValue::Counter(v) => Some((i64::from(v) as $type, false)),
+                    ScalarValue::Uint(v) => Some((*v as $type, false)),
+                    ScalarValue::Str(v) => v.trim().parse().ok().map(|v| (v, true)),
+                    ScalarValue::Boolean(v) => Some((u8::from(*v) as $type, true)),
+                    _ => None,
+                }
+            }
+        })*
+    };
+}
+
+impl_coerce_float!(f32, f64);
+
+impl Coerce for bool {
+    fn coerce(s: &ScalarValue) -> Option<(Self, bool)> {
+        match s {
+            ScalarValue::Boolean(v) => Some((*v, false)),
+            ScalarValue::Int(v @ (0 | 1)) => Some((*v == 1, true)),
+            ScalarValue::Uint(v @ (0 | 1)) => Some((*v == 1, true)),
+            ScalarValue::Str(v) => v.trim().parse().ok().map(|v| (v, true)),
+            _ => None,
+        }
+    }
+}
+
+impl Coerce for String {
+    fn coerce(s: &ScalarValue) -> Option<(Self, bool)> {
+        match s {
+            ScalarValue::Str(v) => Some((v.to_string(), false)),
+            ScalarValue::Int(v) => Some((v.to_string(), true)),
+            ScalarValue::Uint(v) => Some((v.to_string(), true)),
+            ScalarValue::F64(v) => Some((v.to_string(), true)),
+            ScalarValue::Boolean(v) => Some((v.to_string(), true)),
+            _ => None,
+        }
+    }
+}
+
+/// Reads the fields of a struct from a map, yielding every field even if the map doesn't have it
+/// or isn't there at all.
+pub(crate) struct FieldsDeserializer<'a, Rx: ReadDoc> {
+    doc: &'a Rx,
+    obj: Option<ObjId>,
+    heads: Option<&'a [ChangeHash]>,
+    fields: slice::Iter<'static, &'static str>,
+    current: Option<&'static str>,
+    lenient: Lenient<'a>,
+}
+
+impl<'a, Rx: ReadDoc> FieldsDeserializer<'a, Rx> {
+    /// Read `fields` from `obj`, recording all keys of `obj` that are not one of them.
+    pub(crate) fn new(
+        doc: &'a Rx,
+        obj: Option<ObjId>,
+        heads: Option<&'a [ChangeHash]>,
+        fields: &'static [&'static str],
+        lenient: Lenient<'a>,
+    ) -> Result<Self, Error> {
+        if let Some(obj) = &obj {
+            let keys = match heads {
+                Some(heads) => doc.keys_at(obj, heads).collect::<Vec<_>>(),
+                None => doc.keys(obj).collect(),
+            };
+            for key in keys.into_iter().filter(|k| !fields.contains(&k.as_str())) {
+                let value = get(doc, obj, &key, heads)?;
+                lenient.child(Prop::Map(key)).record(doc, &value, heads)?;
+            }
+        }
+        Ok(Self {
+            doc,
+            obj,
+            heads,
+            fields: fields.iter(),
+            current: None,
+            lenient,
+        })
+    }
+}
+
+fn get<'a, Rx: ReadDoc>(
+    doc: &'a Rx,
+    obj: &ObjId,
+    key: &str,
+    heads: Option<&[ChangeHash]>,
+) -> Result<Option<(Value<'a>, ObjId)>, Error> {
+    Ok(match heads {
+        Some(heads) => doc.get_at(obj, key, heads)?,
+        None => doc.get(obj, key)?,
+    })
+}
+
+impl<'de, Rx: ReadDoc> de::MapAccess<'de> for FieldsDeserializer<'_, Rx> {
+    type Error = Error;
+
+    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>, Self::Error>
+    where
+        K: de::DeserializeSeed<'de>,
+    {
+        let Some(field) = self.fields.next() else {
+            return Ok(None);
+        };
+        self.current = Some(field);
+        seed.deserialize(de::value::BorrowedStrDeserializer::new(field))
+            .map(Some)
+    }
+
+    fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value, Self::Error>
+    where
+        V: de::DeserializeSeed<'de>,
+    {
+        let field = self
+            .current
+            .take()
+            .expect("next_value_seed called before next_key_seed");
+        let value = match &self.obj {
+            Some(obj) => get(self.doc, obj, field, self.heads)?,
+            None => None,
+        };
+        let mut deserializer = ValueDeserializer::new(self.doc, value);
+        deserializer.heads = self.heads;
+        deserializer.lenient = Some(self.lenient.child(Prop::Map(field.to_owned())));
+        seed.deserialize(deserializer)
+    }
+}
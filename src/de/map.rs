@@ -1,15 +1,17 @@
-use super::{Deserializer as ValueDeserializer, Error};
+use super::{Deserializer as ValueDeserializer, Error, KeyDeserializer, Lenient};
 use automerge::{
     iter::{MapRange, MapRangeItem},
-    ObjId, ReadDoc, Value,
+    ChangeHash, ObjId, Prop, ReadDoc, Value,
 };
-use serde::de::{self, IntoDeserializer};
+use serde::de;
 use std::ops::RangeFull;
 
 pub struct MapDeserializer<'a, Rx: ReadDoc> {
     doc: &'a Rx,
     values: MapRange<'a, RangeFull>,
-    current: Option<(Value<'a>, ObjId)>,
+    current: Option<(&'a str, Value<'a>, ObjId)>,
+    heads: Option<&'a [ChangeHash]>,
+    lenient: Option<Lenient<'a>>,
 }
 
 impl<'a, Rx: ReadDoc> MapDeserializer<'a, Rx> {
@@ -18,11 +20,26 @@ impl<'a, Rx: ReadDoc> MapDeserializer<'a, Rx> {
             doc,
             values: doc.map_range(id, ..),
             current: None,
+            heads: None,
+            lenient: None,
+        }
+    }
+    pub fn new_at(doc: &'a Rx, id: ObjId, heads: &'a [ChangeHash]) -> Self {
+        Self {
+            doc,
+            values: doc.map_range_at(id, .., heads),
+            current: None,
+            heads: Some(heads),
+            lenient: None,
         }
     }
     pub fn new_root(doc: &'a Rx) -> Self {
         Self::new(doc, ObjId::Root)
     }
+    pub(crate) fn with_lenient(mut self, lenient: Option<Lenient<'a>>) -> Self {
+        self.lenient = lenient;
+        self
+    }
 }
 
 impl<'a, Rx: ReadDoc> From<&'a Rx> for MapDeserializer<'a, Rx> {
@@ -45,8 +62,8 @@ impl<'de, 'a, Rx: ReadDoc> de::MapAccess<'de> for MapDeserializer<'a, Rx> {
             conflict: _,
         }) = self.values.next()
         {
-            self.current = Some((value, id));
-            seed.deserialize(key.into_deserializer()).map(Some)
+            self.current = Some((key, value, id));
+            seed.deserialize(KeyDeserializer::new(key)).map(Some)
         } else {
             Ok(None)
         }
@@ -56,10 +73,16 @@ impl<'de, 'a, Rx: ReadDoc> de::MapAccess<'de> for MapDeserializer<'a, Rx> {
     where
         V: de::DeserializeSeed<'de>,
     {
-        let (value, id) = self
+        let (key, value, id) = self
             .current
             .take()
             .expect("next_value_seed called before next_key_seed");
-        seed.deserialize(ValueDeserializer::new_found(self.doc, value, id))
+        let mut deserializer = ValueDeserializer::new_found(self.doc, value, id);
+        deserializer.heads = self.heads;
+        deserializer.lenient = self
+            .lenient
+            .as_ref()
+            .map(|l| l.child(Prop::Map(key.into())));
+        seed.deserialize(deserializer)
     }
 }
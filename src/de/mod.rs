@@ -1,22 +1,46 @@
-use automerge::{AutomergeError, ObjId, ObjType, Prop, ReadDoc, ScalarValue, Value};
+use crate::{
+    attributed::ATTRIBUTED,
+    text::{replay, BlockText, RichText, BLOCK_TEXT, RICH_TEXT},
+};
+use automerge::{AutomergeError, ChangeHash, ObjId, ObjType, Prop, ReadDoc, ScalarValue, Value};
 use serde::{de, forward_to_deserialize_any};
 
+mod attributed;
 mod error;
+mod key;
+mod lenient;
 mod map;
 mod seq;
 
+use attributed::AttributedDeserializer;
 pub use error::Error;
+pub use key::KeyDeserializer;
+use lenient::{Coerce, FieldsDeserializer};
+pub(crate) use lenient::{Found, Lenient};
 pub use map::MapDeserializer;
 pub use seq::SeqDeserializer;
 
+/// Deserializes `value` of `doc`, see [`Deserializer::new()`].
 pub struct Deserializer<'a, Rx: ReadDoc> {
     pub doc: &'a Rx,
     pub value: Option<(Value<'a>, ObjId)>,
+    /// Read the document as it was at these heads instead of its current state, see
+    /// [`with_heads()`][Self::with_heads()].
+    heads: Option<&'a [ChangeHash]>,
+    /// Convert or replace values that don't fit the type instead of failing, see
+    /// [`repair()`][crate::repair::repair()].
+    lenient: Option<Lenient<'a>>,
 }
 
 impl<'a, Rx: ReadDoc> Deserializer<'a, Rx> {
+    /// Read `value`, as returned by [`ReadDoc::get()`], where [`None`] is a missing value.
     pub fn new(doc: &'a Rx, value: Option<(Value<'a>, ObjId)>) -> Self {
-        Self { doc, value }
+        Self {
+            doc,
+            value,
+            heads: None,
+            lenient: None,
+        }
     }
     pub fn new_found(doc: &'a Rx, value: Value<'a>, id: ObjId) -> Self {
         Self::new(doc, Some((value, id)))
@@ -31,6 +55,65 @@ impl<'a, Rx: ReadDoc> Deserializer<'a, Rx> {
     ) -> Result<Self, AutomergeError> {
         Ok(Self::new(doc, doc.get(key, prop)?))
     }
+    pub fn new_get_at<O: AsRef<ObjId>, P: Into<Prop>>(
+        doc: &'a Rx,
+        key: O,
+        prop: P,
+        heads: &'a [ChangeHash],
+    ) -> Result<Self, AutomergeError> {
+        Ok(Self::new(doc, doc.get_at(key, prop, heads)?).with_heads(heads))
+    }
+    /// Read `value` and everything below it as it was at `heads`.
+    ///
+    /// `value` itself has to be obtained at the same `heads`, e.g. through
+    /// [`ReadDoc::get_at()`].
+    pub fn with_heads(mut self, heads: &'a [ChangeHash]) -> Self {
+        self.heads = Some(heads);
+        self
+    }
+    /// Convert or replace values that don't fit the type, recording them in `lenient`.
+    pub(crate) fn with_lenient(mut self, lenient: Option<Lenient<'a>>) -> Self {
+        self.lenient = lenient;
+        self
+    }
+
+    /// The value as `N`, or its default if it can't be converted.
+    fn coerce<N: Coerce>(self) -> Result<N, Error> {
+        let lenient = self
+            .lenient
+            .as_ref()
+            .expect("coerce called on a strict deserializer");
+        let coerced = match &self.value {
+            Some((Value::Scalar(s), _)) => N::coerce(s),
+            _ => None,
+        };
+        if !matches!(coerced, Some((_, false))) {
+            lenient.record(self.doc, &self.value, self.heads)?;
+        }
+        Ok(coerced.map(|(v, _)| v).unwrap_or_default())
+    }
+    /// Record the value that doesn't fit the type, if this is a lenient deserializer.
+    fn mismatch(&self) -> Result<bool, Error> {
+        let Some(lenient) = &self.lenient else {
+            return Ok(false);
+        };
+        lenient.record(self.doc, &self.value, self.heads)?;
+        Ok(true)
+    }
+}
+
+macro_rules! deserialize_coerced {
+    ($($method:ident => $visit:ident),* $(,)?) => {
+        $(fn $method<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+        where
+            V: de::Visitor<'de>,
+        {
+            if self.lenient.is_none() {
+                return self.deserialize_any(visitor);
+            }
+            visitor.$visit(self.coerce()?)
+        })*
+    };
 }
 
 impl<'a, Rx: ReadDoc> From<&'a Rx> for Deserializer<'a, Rx> {
@@ -39,6 +122,14 @@ impl<'a, Rx: ReadDoc> From<&'a Rx> for Deserializer<'a, Rx> {
     }
 }
 
+impl<'de, Rx: ReadDoc> de::IntoDeserializer<'de, Error> for Deserializer<'_, Rx> {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
 impl<'de, Rx: ReadDoc> de::Deserializer<'de> for Deserializer<'_, Rx> {
     type Error = Error;
 
@@ -48,11 +139,22 @@ impl<'de, Rx: ReadDoc> de::Deserializer<'de> for Deserializer<'_, Rx> {
     {
         match self.value {
             None => visitor.visit_none(),
-            Some((Value::Object(t), id)) => match t {
-                ObjType::List => visitor.visit_seq(SeqDeserializer::new(self.doc, id)),
-                ObjType::Text => visitor.visit_string(self.doc.text(id)?),
-                ObjType::Map | ObjType::Table => {
-                    visitor.visit_map(MapDeserializer::new(self.doc, id))
+            Some((Value::Object(t), id)) => match (t, self.heads) {
+                (ObjType::List, None) => {
+                    visitor.visit_seq(SeqDeserializer::new(self.doc, id).with_lenient(self.lenient))
+                }
+                (ObjType::List, Some(heads)) => {
+                    let seq = SeqDeserializer::new_at(self.doc, id, heads);
+                    visitor.visit_seq(seq.with_lenient(self.lenient))
+                }
+                (ObjType::Text, None) => visitor.visit_string(self.doc.text(id)?),
+                (ObjType::Text, Some(heads)) => visitor.visit_string(self.doc.text_at(id, heads)?),
+                (ObjType::Map | ObjType::Table, None) => {
+                    visitor.visit_map(MapDeserializer::new(self.doc, id).with_lenient(self.lenient))
+                }
+                (ObjType::Map | ObjType::Table, Some(heads)) => {
+                    let map = MapDeserializer::new_at(self.doc, id, heads);
+                    visitor.visit_map(map.with_lenient(self.lenient))
                 }
             },
             Some((Value::Scalar(s), _)) => match s.into_owned() {
@@ -73,9 +175,132 @@ impl<'de, Rx: ReadDoc> de::Deserializer<'de> for Deserializer<'_, Rx> {
         }
     }
 
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        match &self.value {
+            // A missing optional value fits the type, so it is not recorded
+            None => visitor.visit_none(),
+            Some((Value::Scalar(s), _)) if s.is_null() => visitor.visit_none(),
+            Some(_) => visitor.visit_some(self),
+        }
+    }
+
+    fn deserialize_newtype_struct<V>(
+        self,
+        name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        match self.value {
+            Some((Value::Object(ObjType::Text), id)) if name == RICH_TEXT => {
+                replay(&RichText::read(self.doc, &id, self.heads)?.spans, visitor)
+            }
+            Some((Value::Object(ObjType::Text), id)) if name == BLOCK_TEXT => replay(
+                &BlockText::read(self.doc, &id, self.heads)?.fields(),
+                visitor,
+            ),
+            Some((value, id @ ObjId::Id(..))) if name == ATTRIBUTED => {
+                let op = id.to_bytes();
+                let deserializer = Deserializer {
+                    value: Some((value, id)),
+                    ..self
+                };
+                visitor.visit_seq(AttributedDeserializer::new(op, deserializer))
+            }
+            _ => visitor.visit_newtype_struct(self),
+        }
+    }
+
+    deserialize_coerced! {
+        deserialize_bool => visit_bool,
+        deserialize_i8 => visit_i8,
+        deserialize_i16 => visit_i16,
+        deserialize_i32 => visit_i32,
+        deserialize_i64 => visit_i64,
+        deserialize_u8 => visit_u8,
+        deserialize_u16 => visit_u16,
+        deserialize_u32 => visit_u32,
+        deserialize_u64 => visit_u64,
+        deserialize_f32 => visit_f32,
+        deserialize_f64 => visit_f64,
+    }
+
+    fn deserialize_str<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        self.deserialize_string(visitor)
+    }
+
+    fn deserialize_string<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        match &self.value {
+            Some((Value::Object(ObjType::Text), _)) => self.deserialize_any(visitor),
+            _ if self.lenient.is_some() => visitor.visit_string(self.coerce()?),
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_seq<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        match &self.value {
+            Some((Value::Object(ObjType::List), _)) => self.deserialize_any(visitor),
+            _ if self.mismatch()? => {
+                visitor.visit_seq(de::value::SeqDeserializer::new(std::iter::empty::<()>()))
+            }
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_map<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        match &self.value {
+            Some((Value::Object(ObjType::Map | ObjType::Table), _)) => {
+                self.deserialize_any(visitor)
+            }
+            _ if self.mismatch()? => visitor.visit_map(de::value::MapDeserializer::new(
+                std::iter::empty::<((), ())>(),
+            )),
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_struct<V>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        let Some(lenient) = self.lenient.clone() else {
+            return self.deserialize_any(visitor);
+        };
+        let obj = match &self.value {
+            Some((Value::Object(ObjType::Map | ObjType::Table), id)) => Some(id.clone()),
+            _ => {
+                self.mismatch()?;
+                None
+            }
+        };
+        visitor.visit_map(FieldsDeserializer::new(
+            self.doc, obj, self.heads, fields, lenient,
+        )?)
+    }
+
     forward_to_deserialize_any! {
-        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
-        bytes byte_buf option unit unit_struct newtype_struct seq tuple
-        tuple_struct map struct enum identifier ignored_any
+        i128 u128 char bytes byte_buf unit unit_struct tuple
+        tuple_struct enum identifier ignored_any
     }
 }
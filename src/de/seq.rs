@@ -1,7 +1,7 @@
-use super::{Deserializer as ValueDeserializer, Error};
+use super::{Deserializer as ValueDeserializer, Error, Lenient};
 use automerge::{
     iter::{ListRange, ListRangeItem},
-    ObjId, ReadDoc,
+    ChangeHash, ObjId, Prop, ReadDoc,
 };
 use serde::de::{self};
 use std::ops::RangeFull;
@@ -9,6 +9,8 @@ use std::ops::RangeFull;
 pub struct SeqDeserializer<'a, Rx: ReadDoc> {
     doc: &'a Rx,
     values: ListRange<'a, RangeFull>,
+    heads: Option<&'a [ChangeHash]>,
+    lenient: Option<Lenient<'a>>,
 }
 
 impl<'a, Rx: ReadDoc> SeqDeserializer<'a, Rx> {
@@ -16,8 +18,22 @@ impl<'a, Rx: ReadDoc> SeqDeserializer<'a, Rx> {
         Self {
             doc,
             values: doc.list_range(id, ..),
+            heads: None,
+            lenient: None,
         }
     }
+    pub fn new_at(doc: &'a Rx, id: ObjId, heads: &'a [ChangeHash]) -> Self {
+        Self {
+            doc,
+            values: doc.list_range_at(id, .., heads),
+            heads: Some(heads),
+            lenient: None,
+        }
+    }
+    pub(crate) fn with_lenient(mut self, lenient: Option<Lenient<'a>>) -> Self {
+        self.lenient = lenient;
+        self
+    }
 }
 
 impl<'de, 'a, Rx: ReadDoc> de::SeqAccess<'de> for SeqDeserializer<'a, Rx> {
@@ -27,9 +43,14 @@ impl<'de, 'a, Rx: ReadDoc> de::SeqAccess<'de> for SeqDeserializer<'a, Rx> {
     where
         T: de::DeserializeSeed<'de>,
     {
-        if let Some(ListRangeItem { value, id, .. }) = self.values.next() {
-            seed.deserialize(ValueDeserializer::new_found(self.doc, value, id))
-                .map(Some)
+        if let Some(ListRangeItem {
+            index, value, id, ..
+        }) = self.values.next()
+        {
+            let mut deserializer = ValueDeserializer::new_found(self.doc, value, id);
+            deserializer.heads = self.heads;
+            deserializer.lenient = self.lenient.as_ref().map(|l| l.child(Prop::Seq(index)));
+            seed.deserialize(deserializer).map(Some)
         } else {
             Ok(None)
         }
@@ -0,0 +1,132 @@
+pub use crate::value::FieldValue;
+use crate::{patch::targets, AutomergeSerdeError, Deserializer};
+use automerge::{
+    patches::TextRepresentation, Automerge, ChangeHash, ObjId, ObjType, Prop, ReadDoc, Value,
+};
+use serde::{de::DeserializeOwned, Deserialize};
+use std::fmt;
+
+/// Format `path` as `position.x` or `names[2]`.
+pub fn format_path(path: &[Prop]) -> String {
+    let mut formatted = String::new();
+    for prop in path {
+        match prop {
+            Prop::Map(key) if formatted.is_empty() => formatted.push_str(key),
+            Prop::Map(key) => {
+                formatted.push('.');
+                formatted.push_str(key);
+            }
+            Prop::Seq(index) => formatted.push_str(&format!("[{index}]")),
+        }
+    }
+    formatted
+}
+
+/// A value at `path` that changed between two sets of heads.
+///
+/// `old` is [`None`] if the value was added and `new` is [`None`] if it was removed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldChange {
+    pub path: Vec<Prop>,
+    pub old: Option<FieldValue>,
+    pub new: Option<FieldValue>,
+}
+
+impl fmt::Display for FieldChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let value = |v: &Option<FieldValue>| v.as_ref().map_or("-".to_owned(), |v| v.to_string());
+        write!(
+            f,
+            "{}: {} → {}",
+            format_path(&self.path),
+            value(&self.old),
+            value(&self.new)
+        )
+    }
+}
+
+/// The typed values at both sets of heads and the individual values that changed between them.
+///
+/// Only `before` and `after` are typed. The `changes` are read at the paths that changed, which
+/// can be anywhere inside of `T`, so their values are dynamic [`FieldValue`]s.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TypedDiff<T> {
+    pub before: T,
+    pub after: T,
+    pub changes: Vec<FieldChange>,
+}
+
+/// Look up the value at `path` below the root, as it was at `heads` if given.
+pub(crate) fn get_path<'a, Rx: ReadDoc>(
+    doc: &'a Rx,
+    path: &[Prop],
+    heads: Option<&[ChangeHash]>,
+) -> Result<Option<(Value<'a>, ObjId)>, AutomergeSerdeError> {
+    get_path_in(doc, ObjId::Root, path, heads)
+}
+
+/// Look up the value at `path` below the object `obj`, as it was at `heads` if given.
+pub(crate) fn get_path_in<'a, Rx: ReadDoc>(
+    doc: &'a Rx,
+    obj: ObjId,
+    path: &[Prop],
+    heads: Option<&[ChangeHash]>,
+) -> Result<Option<(Value<'a>, ObjId)>, AutomergeSerdeError> {
+    // The type of `obj` itself is never looked at, only that it is an object
+    let mut node = Some((Value::Object(ObjType::Map), obj));
+    for prop in path {
+        node = match (node, heads) {
+            (Some((Value::Object(_), obj)), Some(heads)) => doc.get_at(obj, prop.clone(), heads)?,
+            (Some((Value::Object(_), obj)), None) => doc.get(obj, prop.clone())?,
+            _ => return Ok(None),
+        };
+    }
+    Ok(node)
+}
+
+fn read_path_at<Rx: ReadDoc>(
+    doc: &Rx,
+    path: &[Prop],
+    heads: &[ChangeHash],
+) -> Result<Option<FieldValue>, AutomergeSerdeError> {
+    get_path(doc, path, Some(heads))?
+        .map(|node| FieldValue::deserialize(Deserializer::new(doc, Some(node)).with_heads(heads)))
+        .transpose()
+        .map_err(Into::into)
+}
+
+/// The values that changed in `doc` between `before` and `after`, without deserializing the
+/// document as a whole.
+pub fn field_changes(
+    doc: &Automerge,
+    before: &[ChangeHash],
+    after: &[ChangeHash],
+) -> Result<Vec<FieldChange>, AutomergeSerdeError> {
+    let patches = doc.diff(
+        before,
+        after,
+        TextRepresentation::String(doc.text_encoding()),
+    );
+    targets(patches)
+        .into_iter()
+        .map(|path| {
+            let old = read_path_at(doc, &path, before)?;
+            let new = read_path_at(doc, &path, after)?;
+            Ok(FieldChange { path, old, new })
+        })
+        .collect()
+}
+
+/// Compute the changes to the value of type `T` at the root of `doc` between `before` and `after`.
+pub fn diff_typed<T: DeserializeOwned>(
+    doc: &Automerge,
+    before: &[ChangeHash],
+    after: &[ChangeHash],
+) -> Result<TypedDiff<T>, AutomergeSerdeError> {
+    let changes = field_changes(doc, before, after)?;
+    Ok(TypedDiff {
+        before: T::deserialize(Deserializer::new_root(doc).with_heads(before))?,
+        after: T::deserialize(Deserializer::new_root(doc).with_heads(after))?,
+        changes,
+    })
+}
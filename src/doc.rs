@@ -0,0 +1,135 @@
+use crate::{
+    diff::{diff_typed, TypedDiff},
+    history::{history, History},
+    merge::{preview_merge, MergePreview},
+    observe::{NotifyErrors, Observers},
+    patch::{apply_patches, ApplyPatch},
+    AutomergeSerdeError, Deserializer, Serializer,
+};
+use automerge::{AutoCommit, ChangeHash, ObjId};
+use serde::{de::DeserializeOwned, Serialize};
+use std::marker::PhantomData;
+
+/// An [`AutoCommit`] document whose root map holds a value of type `T`.
+pub struct TypedDoc<T> {
+    doc: AutoCommit,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T: Serialize + DeserializeOwned> TypedDoc<T> {
+    pub fn new(value: &T) -> Result<Self, AutomergeSerdeError> {
+        let mut doc = Self::from_doc(AutoCommit::new());
+        doc.write(value)?;
+        doc.doc.update_diff_cursor();
+        Ok(doc)
+    }
+    /// Wrap `doc`, whose current state is where [`refresh()`][Self::refresh()] starts from.
+    pub fn from_doc(mut doc: AutoCommit) -> Self {
+        doc.update_diff_cursor();
+        Self {
+            doc,
+            _marker: PhantomData,
+        }
+    }
+    pub fn load(data: &[u8]) -> Result<Self, AutomergeSerdeError> {
+        Ok(Self::from_doc(AutoCommit::load(data)?))
+    }
+
+    pub fn read(&self) -> Result<T, AutomergeSerdeError> {
+        Ok(T::deserialize(Deserializer::new_root(&self.doc))?)
+    }
+    /// Reconcile `value` into the document and commit the result as a single change.
+    ///
+    /// Ops that are still pending from [`doc_mut()`][Self::doc_mut()] are committed first, so
+    /// that a failed write only rolls back its own ops.
+    pub fn write(&mut self, value: &T) -> Result<(), AutomergeSerdeError> {
+        self.doc.commit();
+        let result = value
+            .serialize(Serializer::new_object(&mut self.doc, ObjId::Root).with_reconcile(true));
+        if let Err(e) = result {
+            self.doc.rollback();
+            return Err(e.into());
+        }
+        self.doc.commit();
+        Ok(())
+    }
+    /// Read the current value, let `f` modify it and reconcile the result back into the document.
+    pub fn modify<R>(&mut self, f: impl FnOnce(&mut T) -> R) -> Result<R, AutomergeSerdeError> {
+        let mut value = self.read()?;
+        let result = f(&mut value);
+        self.write(&value)?;
+        Ok(result)
+    }
+    /// Bring `value` up to date with all changes since it was created, loaded or last refreshed,
+    /// only deserializing the parts that changed.
+    pub fn refresh(&mut self, value: &mut T) -> Result<(), AutomergeSerdeError>
+    where
+        T: ApplyPatch,
+    {
+        let patches = self.doc.diff_incremental();
+        apply_patches(value, &self.doc, patches)
+    }
+
+    pub fn save(&mut self) -> Vec<u8> {
+        self.doc.save()
+    }
+    pub fn save_incremental(&mut self) -> Vec<u8> {
+        self.doc.save_incremental()
+    }
+    pub fn load_incremental(&mut self, data: &[u8]) -> Result<usize, AutomergeSerdeError> {
+        Ok(self.doc.load_incremental(data)?)
+    }
+    pub fn merge(&mut self, other: &mut Self) -> Result<Vec<ChangeHash>, AutomergeSerdeError> {
+        Ok(self.doc.merge(&mut other.doc)?)
+    }
+    pub fn fork(&mut self) -> Self {
+        Self::from_doc(self.doc.fork())
+    }
+    /// Fork the document as it was at `heads`.
+    pub fn fork_at(&mut self, heads: &[ChangeHash]) -> Result<Self, AutomergeSerdeError> {
+        Ok(Self::from_doc(self.doc.fork_at(heads)?))
+    }
+    pub fn get_heads(&mut self) -> Vec<ChangeHash> {
+        self.doc.get_heads()
+    }
+    /// Read the value as it was at `heads`.
+    pub fn read_at(&self, heads: &[ChangeHash]) -> Result<T, AutomergeSerdeError> {
+        Ok(T::deserialize(
+            Deserializer::new_root(&self.doc).with_heads(heads),
+        )?)
+    }
+    /// See [`diff_typed()`].
+    pub fn diff(
+        &mut self,
+        before: &[ChangeHash],
+        after: &[ChangeHash],
+    ) -> Result<TypedDiff<T>, AutomergeSerdeError> {
+        diff_typed(self.doc.document(), before, after)
+    }
+
+    /// See [`preview_merge()`].
+    pub fn preview_merge(
+        &mut self,
+        incoming: &[u8],
+    ) -> Result<MergePreview<T>, AutomergeSerdeError> {
+        preview_merge(self.doc.document(), incoming)
+    }
+    /// See [`history()`].
+    pub fn history(&mut self) -> History<'_, T> {
+        history(self.doc.document())
+    }
+    /// Notify `observers` of all changes since their last update, see [`Observers::update()`].
+    pub fn notify(&mut self, observers: &mut Observers) -> Result<(), NotifyErrors> {
+        observers.update(self.doc.document())
+    }
+
+    pub fn doc(&self) -> &AutoCommit {
+        &self.doc
+    }
+    pub fn doc_mut(&mut self) -> &mut AutoCommit {
+        &mut self.doc
+    }
+    pub fn into_doc(self) -> AutoCommit {
+        self.doc
+    }
+}
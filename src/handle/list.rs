@@ -0,0 +1,182 @@
+use crate::{AutomergeSerdeError, Deserializer, Serializer};
+use automerge::{
+    iter::ListRangeItem, transaction::Transactable, AutomergeError, ObjId, ObjType, Prop, ReadDoc,
+    ScalarValue, Value,
+};
+use serde::{de::DeserializeOwned, Serialize};
+use std::{
+    marker::PhantomData,
+    ops::{Bound, RangeBounds},
+};
+
+/// A typed view on an automerge list whose elements are all of type `T`.
+///
+/// The handle only stores the [`ObjId`] of the list, the document is passed in to every call so
+/// that the same handle can be used with an [`automerge::AutoCommit`], [`automerge::Automerge`]
+/// or an open [`automerge::transaction::Transaction`].
+pub struct ListHandle<T> {
+    obj: ObjId,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T> Clone for ListHandle<T> {
+    fn clone(&self) -> Self {
+        Self::new(self.obj.clone())
+    }
+}
+
+impl<T> std::fmt::Debug for ListHandle<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("ListHandle").field(&self.obj).finish()
+    }
+}
+
+impl<T> ListHandle<T> {
+    pub fn new(obj: ObjId) -> Self {
+        Self {
+            obj,
+            _marker: PhantomData,
+        }
+    }
+    pub fn obj(&self) -> &ObjId {
+        &self.obj
+    }
+}
+
+impl<T: Serialize + DeserializeOwned> ListHandle<T> {
+    /// Put a new, empty list at `prop` of `obj`.
+    pub fn create<Tx: Transactable, P: Into<Prop>>(
+        tx: &mut Tx,
+        obj: ObjId,
+        prop: P,
+    ) -> Result<Self, AutomergeSerdeError> {
+        Ok(Self::new(tx.put_object(obj, prop, ObjType::List)?))
+    }
+    /// Look up the list at `prop` of `obj`, returning [`None`] if there is no list.
+    pub fn new_get<Rx: ReadDoc, P: Into<Prop>>(
+        doc: &Rx,
+        obj: ObjId,
+        prop: P,
+    ) -> Result<Option<Self>, AutomergeSerdeError> {
+        Ok(match doc.get(obj, prop)? {
+            Some((Value::Object(ObjType::List), id)) => Some(Self::new(id)),
+            _ => None,
+        })
+    }
+    pub fn len<Rx: ReadDoc>(&self, doc: &Rx) -> usize {
+        doc.length(&self.obj)
+    }
+    pub fn is_empty<Rx: ReadDoc>(&self, doc: &Rx) -> bool {
+        self.len(doc) == 0
+    }
+    pub fn get<Rx: ReadDoc>(
+        &self,
+        doc: &Rx,
+        index: usize,
+    ) -> Result<Option<T>, AutomergeSerdeError> {
+        doc.get(&self.obj, index)?
+            .map(|(v, id)| T::deserialize(Deserializer::new_found(doc, v, id)))
+            .transpose()
+            .map_err(Into::into)
+    }
+    pub fn iter<'a, Rx: ReadDoc>(
+        &self,
+        doc: &'a Rx,
+    ) -> impl Iterator<Item = Result<T, AutomergeSerdeError>> + 'a {
+        doc.list_range(&self.obj, ..)
+            .map(move |ListRangeItem { value, id, .. }| {
+                T::deserialize(Deserializer::new_found(doc, value, id)).map_err(Into::into)
+            })
+    }
+    pub fn to_vec<Rx: ReadDoc>(&self, doc: &Rx) -> Result<Vec<T>, AutomergeSerdeError> {
+        self.iter(doc).collect()
+    }
+
+    /// Reconcile `value` into the existing element at `index`.
+    pub fn set<Tx: Transactable>(
+        &self,
+        tx: &mut Tx,
+        index: usize,
+        value: &T,
+    ) -> Result<(), AutomergeSerdeError> {
+        value.serialize(Serializer::new(tx, self.obj.clone(), index).with_reconcile(true))?;
+        Ok(())
+    }
+    pub fn push<Tx: Transactable>(
+        &self,
+        tx: &mut Tx,
+        value: &T,
+    ) -> Result<(), AutomergeSerdeError> {
+        let index = tx.length(&self.obj);
+        self.insert(tx, index, value)
+    }
+    /// Insert `value` at `index`, leaving the list unchanged if it fails to serialize.
+    pub fn insert<Tx: Transactable>(
+        &self,
+        tx: &mut Tx,
+        index: usize,
+        value: &T,
+    ) -> Result<(), AutomergeSerdeError> {
+        tx.insert(&self.obj, index, ScalarValue::Null)?;
+        let result = value
+            .serialize(Serializer::new(tx, self.obj.clone(), index))
+            .map(|_| ());
+        if result.is_err() {
+            // Don't leave the placeholder behind, along with anything written into it
+            tx.delete(&self.obj, index)?;
+        }
+        Ok(result?)
+    }
+    pub fn remove<Tx: Transactable>(
+        &self,
+        tx: &mut Tx,
+        index: usize,
+    ) -> Result<T, AutomergeSerdeError> {
+        let value = self
+            .get(tx, index)?
+            .ok_or(AutomergeError::InvalidIndex(index))?;
+        tx.delete(&self.obj, index)?;
+        Ok(value)
+    }
+    /// Replace the elements in `range` with `values`, returning the removed elements.
+    ///
+    /// Fails without changing the list if `range` is out of bounds.
+    pub fn splice<Tx: Transactable, R: RangeBounds<usize>, I: IntoIterator<Item = T>>(
+        &self,
+        tx: &mut Tx,
+        range: R,
+        values: I,
+    ) -> Result<Vec<T>, AutomergeSerdeError> {
+        let len = tx.length(&self.obj);
+        let after = |i: usize| i.checked_add(1).ok_or(AutomergeError::InvalidIndex(i));
+        let start = match range.start_bound() {
+            Bound::Included(&i) => i,
+            Bound::Excluded(&i) => after(i)?,
+            Bound::Unbounded => 0,
+        };
+        let end = match range.end_bound() {
+            Bound::Included(&i) => after(i)?,
+            Bound::Excluded(&i) => i,
+            Bound::Unbounded => len,
+        };
+        if end > len {
+            return Err(AutomergeError::InvalidIndex(end).into());
+        }
+        if start > end {
+            return Err(AutomergeError::InvalidIndex(start).into());
+        }
+        let removed = tx
+            .list_range(&self.obj, start..end)
+            .map(|ListRangeItem { value, id, .. }| {
+                T::deserialize(Deserializer::new_found(tx, value, id)).map_err(Into::into)
+            })
+            .collect::<Result<Vec<_>, AutomergeSerdeError>>()?;
+        for _ in start..end {
+            tx.delete(&self.obj, start)?;
+        }
+        for (index, value) in (start..).zip(values) {
+            self.insert(tx, index, &value)?;
+        }
+        Ok(removed)
+    }
+}
@@ -0,0 +1,133 @@
+use crate::{
+    de::KeyDeserializer, ser::KeySerializer, AutomergeSerdeError, Deserializer, Serializer,
+};
+use automerge::{
+    iter::MapRangeItem, transaction::Transactable, ObjId, ObjType, Prop, ReadDoc, Value,
+};
+use serde::{de::DeserializeOwned, Serialize};
+use std::marker::PhantomData;
+
+/// A typed view on an automerge map with keys of type `K` and values of type `V`.
+///
+/// Keys are encoded with [`KeySerializer`], so entries are interchangeable with maps written
+/// through [`crate::AutomergeSetExtension::set_value()`].
+pub struct MapHandle<K, V> {
+    obj: ObjId,
+    _marker: PhantomData<fn() -> (K, V)>,
+}
+
+impl<K, V> Clone for MapHandle<K, V> {
+    fn clone(&self) -> Self {
+        Self::new(self.obj.clone())
+    }
+}
+
+impl<K, V> std::fmt::Debug for MapHandle<K, V> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("MapHandle").field(&self.obj).finish()
+    }
+}
+
+impl<K, V> MapHandle<K, V> {
+    pub fn new(obj: ObjId) -> Self {
+        Self {
+            obj,
+            _marker: PhantomData,
+        }
+    }
+    pub fn obj(&self) -> &ObjId {
+        &self.obj
+    }
+}
+
+impl<K, V> MapHandle<K, V>
+where
+    K: Serialize + DeserializeOwned,
+    V: Serialize + DeserializeOwned,
+{
+    /// Put a new, empty map at `prop` of `obj`.
+    pub fn create<Tx: Transactable, P: Into<Prop>>(
+        tx: &mut Tx,
+        obj: ObjId,
+        prop: P,
+    ) -> Result<Self, AutomergeSerdeError> {
+        Ok(Self::new(tx.put_object(obj, prop, ObjType::Map)?))
+    }
+    /// Look up the map at `prop` of `obj`, returning [`None`] if there is no map.
+    pub fn new_get<Rx: ReadDoc, P: Into<Prop>>(
+        doc: &Rx,
+        obj: ObjId,
+        prop: P,
+    ) -> Result<Option<Self>, AutomergeSerdeError> {
+        Ok(match doc.get(obj, prop)? {
+            Some((Value::Object(ObjType::Map | ObjType::Table), id)) => Some(Self::new(id)),
+            _ => None,
+        })
+    }
+
+    fn key(key: &K) -> Result<String, AutomergeSerdeError> {
+        Ok(key.serialize(KeySerializer)?)
+    }
+
+    pub fn len<Rx: ReadDoc>(&self, doc: &Rx) -> usize {
+        doc.length(&self.obj)
+    }
+    pub fn is_empty<Rx: ReadDoc>(&self, doc: &Rx) -> bool {
+        self.len(doc) == 0
+    }
+    pub fn contains_key<Rx: ReadDoc>(
+        &self,
+        doc: &Rx,
+        key: &K,
+    ) -> Result<bool, AutomergeSerdeError> {
+        Ok(doc.get(&self.obj, Self::key(key)?)?.is_some())
+    }
+    pub fn get<Rx: ReadDoc>(&self, doc: &Rx, key: &K) -> Result<Option<V>, AutomergeSerdeError> {
+        doc.get(&self.obj, Self::key(key)?)?
+            .map(|(v, id)| V::deserialize(Deserializer::new_found(doc, v, id)))
+            .transpose()
+            .map_err(Into::into)
+    }
+    pub fn keys<'a, Rx: ReadDoc>(
+        &self,
+        doc: &'a Rx,
+    ) -> impl Iterator<Item = Result<K, AutomergeSerdeError>> + 'a {
+        doc.keys(&self.obj)
+            .map(|key| K::deserialize(KeyDeserializer::new(&key)).map_err(Into::into))
+    }
+    pub fn iter<'a, Rx: ReadDoc>(
+        &self,
+        doc: &'a Rx,
+    ) -> impl Iterator<Item = Result<(K, V), AutomergeSerdeError>> + 'a {
+        doc.map_range(&self.obj, ..)
+            .map(move |MapRangeItem { key, value, id, .. }| {
+                let key = K::deserialize(KeyDeserializer::new(key))?;
+                let value = V::deserialize(Deserializer::new_found(doc, value, id))?;
+                Ok((key, value))
+            })
+    }
+
+    /// Reconcile `value` into the entry at `key`, creating it if it does not exist yet.
+    pub fn insert<Tx: Transactable>(
+        &self,
+        tx: &mut Tx,
+        key: &K,
+        value: &V,
+    ) -> Result<(), AutomergeSerdeError> {
+        value.serialize(
+            Serializer::new(tx, self.obj.clone(), Self::key(key)?).with_reconcile(true),
+        )?;
+        Ok(())
+    }
+    pub fn remove<Tx: Transactable>(
+        &self,
+        tx: &mut Tx,
+        key: &K,
+    ) -> Result<Option<V>, AutomergeSerdeError> {
+        let value = self.get(tx, key)?;
+        if value.is_some() {
+            tx.delete(&self.obj, Self::key(key)?)?;
+        }
+        Ok(value)
+    }
+}
@@ -0,0 +1,8 @@
+mod list;
+mod map;
+mod text;
+
+pub use list::ListHandle;
+pub use map::MapHandle;
+pub(crate) use text::width;
+pub use text::{convert_index, TextHandle};
@@ -0,0 +1,188 @@
+use crate::AutomergeSerdeError;
+use automerge::{
+    transaction::Transactable, AutomergeError, Cursor, MoveCursor, ObjId, ObjType, Prop, ReadDoc,
+    TextEncoding, Value,
+};
+use std::ops::Range;
+use unicode_segmentation::UnicodeSegmentation;
+
+/// A view on an automerge [`ObjType::Text`] object.
+///
+/// Indices passed to and returned from the editing methods are in the [`TextEncoding`] of the
+/// document. Use [`TextHandle::to_encoding()`] and [`TextHandle::from_encoding()`] when talking to
+/// an editor that counts in a different unit, e.g. UTF-16 code units in JavaScript.
+#[derive(Debug, Clone)]
+pub struct TextHandle {
+    obj: ObjId,
+}
+
+impl TextHandle {
+    pub fn new(obj: ObjId) -> Self {
+        Self { obj }
+    }
+    /// Put a new, empty text object at `prop` of `obj`.
+    pub fn create<Tx: Transactable, P: Into<Prop>>(
+        tx: &mut Tx,
+        obj: ObjId,
+        prop: P,
+    ) -> Result<Self, AutomergeSerdeError> {
+        Ok(Self::new(tx.put_object(obj, prop, ObjType::Text)?))
+    }
+    /// Look up the text object at `prop` of `obj`, returning [`None`] if there is no text object.
+    pub fn new_get<Rx: ReadDoc, P: Into<Prop>>(
+        doc: &Rx,
+        obj: ObjId,
+        prop: P,
+    ) -> Result<Option<Self>, AutomergeSerdeError> {
+        Ok(match doc.get(obj, prop)? {
+            Some((Value::Object(ObjType::Text), id)) => Some(Self::new(id)),
+            _ => None,
+        })
+    }
+    pub fn obj(&self) -> &ObjId {
+        &self.obj
+    }
+
+    pub fn text<Rx: ReadDoc>(&self, doc: &Rx) -> Result<String, AutomergeSerdeError> {
+        Ok(doc.text(&self.obj)?)
+    }
+    /// The length of the text in the [`TextEncoding`] of the document.
+    pub fn len<Rx: ReadDoc>(&self, doc: &Rx) -> usize {
+        doc.length(&self.obj)
+    }
+    pub fn is_empty<Rx: ReadDoc>(&self, doc: &Rx) -> bool {
+        self.len(doc) == 0
+    }
+
+    pub fn splice<Tx: Transactable>(
+        &self,
+        tx: &mut Tx,
+        pos: usize,
+        del: isize,
+        text: &str,
+    ) -> Result<(), AutomergeSerdeError> {
+        Ok(tx.splice_text(&self.obj, pos, del, text)?)
+    }
+    pub fn insert_str<Tx: Transactable>(
+        &self,
+        tx: &mut Tx,
+        pos: usize,
+        text: &str,
+    ) -> Result<(), AutomergeSerdeError> {
+        self.splice(tx, pos, 0, text)
+    }
+    pub fn delete_range<Tx: Transactable>(
+        &self,
+        tx: &mut Tx,
+        range: Range<usize>,
+    ) -> Result<(), AutomergeSerdeError> {
+        let del = range.len() as isize;
+        self.splice(tx, range.start, del, "")
+    }
+    /// Replace the whole text, diffing against the current value to produce minimal splices.
+    pub fn update<Tx: Transactable>(
+        &self,
+        tx: &mut Tx,
+        text: &str,
+    ) -> Result<(), AutomergeSerdeError> {
+        Ok(tx.update_text(&self.obj, text)?)
+    }
+
+    /// Convert `index` from the [`TextEncoding`] of the document into `encoding`.
+    pub fn to_encoding<Rx: ReadDoc>(
+        &self,
+        doc: &Rx,
+        index: usize,
+        encoding: TextEncoding,
+    ) -> Result<usize, AutomergeSerdeError> {
+        convert_index(&doc.text(&self.obj)?, index, doc.text_encoding(), encoding)
+            .ok_or(AutomergeError::InvalidIndex(index).into())
+    }
+    /// Convert `index` from `encoding` into the [`TextEncoding`] of the document.
+    pub fn from_encoding<Rx: ReadDoc>(
+        &self,
+        doc: &Rx,
+        index: usize,
+        encoding: TextEncoding,
+    ) -> Result<usize, AutomergeSerdeError> {
+        convert_index(&doc.text(&self.obj)?, index, encoding, doc.text_encoding())
+            .ok_or(AutomergeError::InvalidIndex(index).into())
+    }
+
+    /// Create a [`Cursor`] for `index` that keeps pointing at the same character when concurrent
+    /// edits are merged in.
+    pub fn cursor<Rx: ReadDoc>(
+        &self,
+        doc: &Rx,
+        index: usize,
+    ) -> Result<Cursor, AutomergeSerdeError> {
+        Ok(doc.get_cursor(&self.obj, index, None)?)
+    }
+    pub fn cursor_moving<Rx: ReadDoc>(
+        &self,
+        doc: &Rx,
+        index: usize,
+        move_cursor: MoveCursor,
+    ) -> Result<Cursor, AutomergeSerdeError> {
+        Ok(doc.get_cursor_moving(&self.obj, index, None, move_cursor)?)
+    }
+    /// Resolve a [`Cursor`] back into an index in the [`TextEncoding`] of the document.
+    pub fn cursor_position<Rx: ReadDoc>(
+        &self,
+        doc: &Rx,
+        cursor: &Cursor,
+    ) -> Result<usize, AutomergeSerdeError> {
+        Ok(doc.get_cursor_position(&self.obj, cursor, None)?)
+    }
+}
+
+pub(crate) fn width(text: &str, encoding: TextEncoding) -> usize {
+    match encoding {
+        TextEncoding::UnicodeCodePoint => text.chars().count(),
+        TextEncoding::Utf8CodeUnit => text.len(),
+        TextEncoding::Utf16CodeUnit => text.encode_utf16().count(),
+        TextEncoding::GraphemeCluster => text.graphemes(true).count(),
+    }
+}
+
+fn byte_offset(text: &str, index: usize, encoding: TextEncoding) -> Option<usize> {
+    let segments: Box<dyn Iterator<Item = (usize, &str)>> = match encoding {
+        TextEncoding::Utf8CodeUnit => {
+            return text.is_char_boundary(index).then_some(index);
+        }
+        TextEncoding::GraphemeCluster => Box::new(text.grapheme_indices(true)),
+        _ => Box::new(
+            text.char_indices()
+                .map(|(offset, c)| (offset, &text[offset..offset + c.len_utf8()])),
+        ),
+    };
+    let mut position = 0;
+    for (offset, segment) in segments {
+        if position == index {
+            return Some(offset);
+        }
+        position += width(segment, encoding);
+        if position > index {
+            return None;
+        }
+    }
+    (position == index).then_some(text.len())
+}
+
+/// Convert `index` into `text` from one [`TextEncoding`] into another.
+///
+/// Returns [`None`] if `index` is out of bounds or does not fall on a boundary in `to`, e.g. in
+/// the middle of a UTF-16 surrogate pair.
+pub fn convert_index(
+    text: &str,
+    index: usize,
+    from: TextEncoding,
+    to: TextEncoding,
+) -> Option<usize> {
+    let offset = byte_offset(text, index, from)?;
+    let prefix = &text[..offset];
+    if to == TextEncoding::GraphemeCluster && byte_offset(text, width(prefix, to), to)? != offset {
+        return None;
+    }
+    Some(width(prefix, to))
+}
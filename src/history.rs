@@ -0,0 +1,123 @@
+use crate::{diff::get_path, AutomergeSerdeError, Deserializer};
+use automerge::{ActorId, Automerge, Change, ChangeHash, Prop};
+use serde::de::DeserializeOwned;
+use std::marker::PhantomData;
+
+/// Metadata of the change that a snapshot was taken after.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChangeMeta {
+    pub hash: ChangeHash,
+    pub actor: ActorId,
+    pub seq: u64,
+    /// Milliseconds since the Unix epoch, as recorded by the author of the change.
+    pub timestamp: i64,
+    pub message: Option<String>,
+    /// The heads of the document once this change and all changes before it were applied.
+    pub heads: Vec<ChangeHash>,
+}
+
+impl ChangeMeta {
+    pub(crate) fn new(change: &Change, heads: &[ChangeHash]) -> Self {
+        Self {
+            hash: change.hash(),
+            actor: change.actor_id().clone(),
+            seq: change.seq(),
+            timestamp: change.timestamp(),
+            message: change.message().cloned(),
+            heads: heads.to_vec(),
+        }
+    }
+}
+
+/// Update the `heads` of a document with `change` applied on top of it.
+pub(crate) fn advance(heads: &mut Vec<ChangeHash>, change: &Change) {
+    heads.retain(|h| !change.deps().contains(h));
+    heads.push(change.hash());
+}
+
+/// Which changes in the history of a document produce a snapshot.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum Step {
+    /// A snapshot after every change.
+    #[default]
+    Change,
+    /// A snapshot after every change made by this actor.
+    Actor(ActorId),
+    /// A snapshot after the last change in every window of this many milliseconds.
+    Window(i64),
+}
+
+/// An iterator over typed snapshots of the history of a document, created with [`history()`].
+///
+/// Changes are visited in the order they were applied to the document and every snapshot is
+/// deserialized with historical reads at the heads after its change.
+pub struct History<'a, T> {
+    doc: &'a Automerge,
+    changes: std::iter::Peekable<std::vec::IntoIter<&'a Change>>,
+    heads: Vec<ChangeHash>,
+    step: Step,
+    path: Vec<Prop>,
+    _marker: PhantomData<fn() -> T>,
+}
+
+/// Iterate over snapshots of the value of type `T` at the root of `doc`.
+pub fn history<T: DeserializeOwned>(doc: &Automerge) -> History<'_, T> {
+    History {
+        doc,
+        changes: doc.get_changes(&[]).into_iter().peekable(),
+        heads: Vec::new(),
+        step: Step::Change,
+        path: Vec::new(),
+        _marker: PhantomData,
+    }
+}
+
+impl<'a, T: DeserializeOwned> History<'a, T> {
+    pub fn step(mut self, step: Step) -> Self {
+        self.step = step;
+        self
+    }
+    /// Only deserialize the value of type `U` at `path` below the root, which is deserialized from
+    /// nothing if it did not exist at the time.
+    pub fn path<U: DeserializeOwned>(self, path: Vec<Prop>) -> History<'a, U> {
+        History {
+            doc: self.doc,
+            changes: self.changes,
+            heads: self.heads,
+            step: self.step,
+            path,
+            _marker: PhantomData,
+        }
+    }
+
+    fn snapshot(&self, change: &Change) -> Result<(ChangeMeta, T), AutomergeSerdeError> {
+        let node = get_path(self.doc, &self.path, Some(&self.heads))?;
+        let value = T::deserialize(Deserializer::new(self.doc, node).with_heads(&self.heads))?;
+        Ok((ChangeMeta::new(change, &self.heads), value))
+    }
+}
+
+impl<'a, T: DeserializeOwned> Iterator for History<'a, T> {
+    type Item = Result<(ChangeMeta, T), AutomergeSerdeError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(change) = self.changes.next() {
+            advance(&mut self.heads, change);
+            let take = match &self.step {
+                Step::Change => true,
+                Step::Actor(actor) => change.actor_id() == actor,
+                Step::Window(millis) => match self.changes.peek() {
+                    Some(next) => {
+                        let millis = (*millis).max(1);
+                        next.timestamp().div_euclid(millis) != change.timestamp().div_euclid(millis)
+                    }
+                    None => true,
+                },
+            };
+            if take {
+                return Some(self.snapshot(change));
+            }
+        }
+        None
+    }
+}
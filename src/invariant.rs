@@ -0,0 +1,129 @@
+use crate::{AutomergeSerdeError, TypedDoc};
+use serde::{de::DeserializeOwned, Serialize};
+
+/// An invariant that did not hold.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Violation {
+    pub invariant: String,
+    pub message: String,
+}
+
+/// The outcome of [`Invariants::enforce()`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Report {
+    /// The invariants that did not hold before repairing.
+    pub violations: Vec<Violation>,
+    /// The invariants that still did not hold after repairing.
+    pub unresolved: Vec<Violation>,
+}
+
+impl Report {
+    pub fn is_clean(&self) -> bool {
+        self.violations.is_empty()
+    }
+}
+
+type Check<T> = Box<dyn Fn(&T) -> Result<(), String>>;
+type Repair<T> = Box<dyn Fn(&mut T)>;
+
+struct Invariant<T> {
+    name: String,
+    check: Check<T>,
+    repair: Repair<T>,
+}
+
+/// Invariants that are checked on the deserialized value after every merge, with repairs for
+/// when concurrent edits break them.
+///
+/// Repairs only see the merged value and their result is reconciled into the document, which
+/// diffs lists by their elements. Every peer that repairs the same merged document therefore
+/// removes and changes the same elements, and the peers converge even if more than one of them
+/// repairs concurrently. Peers that repair different states, e.g. before they received all
+/// changes, may each remove a different copy of a duplicate, so that both are gone after merging.
+/// Repairs should be deterministic, and prefer removing and overwriting elements over inserting new
+/// ones, as concurrent inserts of the same element end up in the document twice.
+pub struct Invariants<T> {
+    invariants: Vec<Invariant<T>>,
+}
+
+impl<T> Default for Invariants<T> {
+    fn default() -> Self {
+        Self {
+            invariants: Vec::new(),
+        }
+    }
+}
+
+impl<T: Serialize + DeserializeOwned> Invariants<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Register an invariant that holds if `check` returns `Ok`, and is restored by `repair`.
+    ///
+    /// Invariants are checked and repaired in the order they are registered.
+    pub fn with(
+        mut self,
+        name: impl Into<String>,
+        check: impl Fn(&T) -> Result<(), String> + 'static,
+        repair: impl Fn(&mut T) + 'static,
+    ) -> Self {
+        self.invariants.push(Invariant {
+            name: name.into(),
+            check: Box::new(check),
+            repair: Box::new(repair),
+        });
+        self
+    }
+
+    pub fn check(&self, value: &T) -> Vec<Violation> {
+        self.invariants
+            .iter()
+            .filter_map(|invariant| {
+                let message = (invariant.check)(value).err()?;
+                Some(Violation {
+                    invariant: invariant.name.clone(),
+                    message,
+                })
+            })
+            .collect()
+    }
+
+    /// Check all invariants on the value of `doc` and commit the repairs of those that don't hold
+    /// as a single change.
+    pub fn enforce(&self, doc: &mut TypedDoc<T>) -> Result<Report, AutomergeSerdeError> {
+        let mut value = doc.read()?;
+        let violations = self.check(&value);
+        if violations.is_empty() {
+            return Ok(Report::default());
+        }
+        for invariant in &self.invariants {
+            if violations.iter().any(|v| v.invariant == invariant.name) {
+                (invariant.repair)(&mut value);
+            }
+        }
+        doc.write(&value)?;
+        Ok(Report {
+            violations,
+            unresolved: self.check(&value),
+        })
+    }
+    /// Merge `other` into `doc` and enforce the invariants on the result.
+    pub fn merge(
+        &self,
+        doc: &mut TypedDoc<T>,
+        other: &mut TypedDoc<T>,
+    ) -> Result<Report, AutomergeSerdeError> {
+        doc.merge(other)?;
+        self.enforce(doc)
+    }
+    /// Load `incoming`, the output of `save()` or `save_incremental()`, into `doc` and enforce the
+    /// invariants on the result.
+    pub fn load_incremental(
+        &self,
+        doc: &mut TypedDoc<T>,
+        incoming: &[u8],
+    ) -> Result<Report, AutomergeSerdeError> {
+        doc.load_incremental(incoming)?;
+        self.enforce(doc)
+    }
+}
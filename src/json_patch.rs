@@ -0,0 +1,414 @@
+//! Conversion between automerge patches and [RFC 6902](https://www.rfc-editor.org/rfc/rfc6902)
+//! JSON Patch operations, and applying JSON Patch and
+//! [RFC 7396](https://www.rfc-editor.org/rfc/rfc7396) JSON Merge Patch documents.
+
+use crate::{value::Node, AutomergeSerdeError, Deserializer, Serializer};
+use automerge::{
+    patches::TextRepresentation, transaction::Transactable, Automerge, ChangeHash, ObjId, ObjType,
+    Patch, PatchAction, Prop, ReadDoc, ScalarValue, Value,
+};
+use serde::{Deserialize, Serialize};
+
+/// A single JSON Patch operation.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "lowercase")]
+pub enum JsonPatchOp {
+    Add {
+        path: String,
+        value: serde_json::Value,
+    },
+    Remove {
+        path: String,
+    },
+    Replace {
+        path: String,
+        value: serde_json::Value,
+    },
+    Move {
+        from: String,
+        path: String,
+    },
+    Copy {
+        from: String,
+        path: String,
+    },
+    Test {
+        path: String,
+        value: serde_json::Value,
+    },
+}
+
+/// Escape `token` for use in a JSON Pointer.
+fn escape(token: &str) -> String {
+    token.replace('~', "~0").replace('/', "~1")
+}
+
+/// Format `path` as a JSON Pointer, such as `/player/names/2`.
+pub fn json_pointer<'a>(path: impl IntoIterator<Item = &'a Prop>) -> String {
+    path.into_iter()
+        .map(|prop| match prop {
+            Prop::Map(key) => format!("/{}", escape(key)),
+            Prop::Seq(index) => format!("/{index}"),
+        })
+        .collect()
+}
+
+/// Render `node` the same way as the [`Deserializer`] would, but only render newly created
+/// objects as empty, as the patches that follow fill them in.
+fn render<Rx: ReadDoc>(
+    doc: &Rx,
+    node: (Value<'_>, ObjId),
+    heads: Option<&[ChangeHash]>,
+) -> Result<serde_json::Value, AutomergeSerdeError> {
+    Ok(match node {
+        (Value::Object(ObjType::Map | ObjType::Table), _) => serde_json::json!({}),
+        (Value::Object(ObjType::List), _) => serde_json::json!([]),
+        (Value::Object(ObjType::Text), _) => serde_json::json!(""),
+        node => read(doc, node, heads)?,
+    })
+}
+
+fn read<Rx: ReadDoc>(
+    doc: &Rx,
+    node: (Value<'_>, ObjId),
+    heads: Option<&[ChangeHash]>,
+) -> Result<serde_json::Value, AutomergeSerdeError> {
+    let deserializer = Deserializer::new(doc, Some(node));
+    Ok(match heads {
+        Some(heads) => serde_json::Value::deserialize(deserializer.with_heads(heads))?,
+        None => serde_json::Value::deserialize(deserializer)?,
+    })
+}
+
+fn convert<Rx: ReadDoc>(
+    doc: &Rx,
+    patches: impl IntoIterator<Item = Patch>,
+    heads: Option<&[ChangeHash]>,
+) -> Result<Vec<JsonPatchOp>, AutomergeSerdeError> {
+    let mut ops = Vec::new();
+    for patch in patches {
+        let path = patch.path.iter().map(|(_, prop)| prop).collect::<Vec<_>>();
+        let child = |prop: Prop| json_pointer(path.iter().copied().chain(std::iter::once(&prop)));
+        let is_text = doc.object_type(&patch.obj)? == ObjType::Text;
+        match patch.action {
+            // Text is rendered as a single string, so replace it as a whole
+            PatchAction::SpliceText { .. } | PatchAction::DeleteSeq { .. } if is_text => {
+                let op = JsonPatchOp::Replace {
+                    path: json_pointer(path),
+                    value: read(doc, (Value::Object(ObjType::Text), patch.obj), heads)?,
+                };
+                if ops.last() != Some(&op) {
+                    ops.push(op);
+                }
+            }
+            // `add` replaces existing members of an object
+            PatchAction::PutMap { key, value, .. } => ops.push(JsonPatchOp::Add {
+                path: child(Prop::Map(key)),
+                value: render(doc, value, heads)?,
+            }),
+            PatchAction::PutSeq { index, value, .. } => ops.push(JsonPatchOp::Replace {
+                path: child(Prop::Seq(index)),
+                value: render(doc, value, heads)?,
+            }),
+            PatchAction::Insert { index, values } => {
+                for (i, (value, id, _)) in values.iter().enumerate() {
+                    ops.push(JsonPatchOp::Add {
+                        path: child(Prop::Seq(index + i)),
+                        value: render(doc, (value.clone(), id.clone()), heads)?,
+                    });
+                }
+            }
+            PatchAction::Increment { prop, .. } => {
+                let value = match heads {
+                    Some(heads) => doc.get_at(&patch.obj, prop.clone(), heads)?,
+                    None => doc.get(&patch.obj, prop.clone())?,
+                };
+                if let Some(value) = value {
+                    ops.push(JsonPatchOp::Replace {
+                        path: child(prop),
+                        value: read(doc, value, heads)?,
+                    });
+                }
+            }
+            PatchAction::DeleteMap { key } => ops.push(JsonPatchOp::Remove {
+                path: child(Prop::Map(key)),
+            }),
+            PatchAction::DeleteSeq { index, length } => {
+                let path = child(Prop::Seq(index));
+                ops.extend((0..length).map(|_| JsonPatchOp::Remove { path: path.clone() }));
+            }
+            // Marks and conflicts have no JSON representation
+            PatchAction::SpliceText { .. }
+            | PatchAction::Conflict { .. }
+            | PatchAction::Mark { .. } => {}
+        }
+    }
+    Ok(ops)
+}
+
+/// Convert `patches`, which lead up to the current state of `doc`, into JSON Patch operations.
+pub fn to_json_patch<Rx: ReadDoc>(
+    doc: &Rx,
+    patches: impl IntoIterator<Item = Patch>,
+) -> Result<Vec<JsonPatchOp>, AutomergeSerdeError> {
+    convert(doc, patches, None)
+}
+
+/// The JSON Patch operations that turn `doc` as it was at `before` into `doc` at `after`.
+pub fn diff_json_patch(
+    doc: &Automerge,
+    before: &[ChangeHash],
+    after: &[ChangeHash],
+) -> Result<Vec<JsonPatchOp>, AutomergeSerdeError> {
+    let patches = doc.diff(
+        before,
+        after,
+        TextRepresentation::String(doc.text_encoding()),
+    );
+    convert(doc, patches, Some(after))
+}
+
+/// Split a JSON Pointer into its unescaped reference tokens.
+fn parse_pointer(pointer: &str) -> Result<Vec<String>, AutomergeSerdeError> {
+    if pointer.is_empty() {
+        return Ok(Vec::new());
+    }
+    let Some(tokens) = pointer.strip_prefix('/') else {
+        return Err(AutomergeSerdeError::InvalidPointer(pointer.to_owned()));
+    };
+    Ok(tokens
+        .split('/')
+        .map(|token| token.replace("~1", "/").replace("~0", "~"))
+        .collect())
+}
+
+/// The property that `token` refers to in `obj`. `-` refers to the end of a list when `append`.
+fn resolve_prop<Tx: Transactable>(
+    tx: &Tx,
+    obj: &ObjId,
+    token: &str,
+    append: bool,
+    pointer: &str,
+) -> Result<Prop, AutomergeSerdeError> {
+    match tx.object_type(obj)? {
+        ObjType::Map | ObjType::Table => Ok(Prop::Map(token.to_owned())),
+        ObjType::List => {
+            let len = tx.length(obj);
+            let index = match token {
+                "-" if append => len,
+                token => token
+                    .parse::<usize>()
+                    .map_err(|_| AutomergeSerdeError::InvalidPointer(pointer.to_owned()))?,
+            };
+            if index < len || (append && index == len) {
+                Ok(Prop::Seq(index))
+            } else {
+                Err(AutomergeSerdeError::NotFound(pointer.to_owned()))
+            }
+        }
+        ObjType::Text => Err(AutomergeSerdeError::NotFound(pointer.to_owned())),
+    }
+}
+
+/// The object that contains the value at `pointer` and the property of that value.
+fn resolve<Tx: Transactable>(
+    tx: &Tx,
+    pointer: &str,
+    append: bool,
+) -> Result<Option<(ObjId, Prop)>, AutomergeSerdeError> {
+    let tokens = parse_pointer(pointer)?;
+    let Some((last, parents)) = tokens.split_last() else {
+        return Ok(None);
+    };
+    let mut obj = ObjId::Root;
+    for token in parents {
+        let prop = resolve_prop(tx, &obj, token, false, pointer)?;
+        obj = match tx.get(&obj, prop)? {
+            Some((Value::Object(_), child)) => child,
+            _ => return Err(AutomergeSerdeError::NotFound(pointer.to_owned())),
+        };
+    }
+    let prop = resolve_prop(tx, &obj, last, append, pointer)?;
+    Ok(Some((obj, prop)))
+}
+
+/// Read the value at `pointer` the same way as the [`Deserializer`] would.
+fn get_json<Tx: Transactable>(
+    tx: &Tx,
+    pointer: &str,
+) -> Result<serde_json::Value, AutomergeSerdeError> {
+    let node = match resolve(tx, pointer, false)? {
+        Some((obj, prop)) => tx
+            .get(&obj, prop)?
+            .ok_or_else(|| AutomergeSerdeError::NotFound(pointer.to_owned()))?,
+        None => (Value::Object(ObjType::Map), ObjId::Root),
+    };
+    serde_json::Value::deserialize(Deserializer::new(tx, Some(node))).map_err(Into::into)
+}
+
+fn add<Tx: Transactable>(
+    tx: &mut Tx,
+    pointer: &str,
+    value: &serde_json::Value,
+) -> Result<(), AutomergeSerdeError> {
+    match resolve(tx, pointer, true)? {
+        Some((obj, Prop::Seq(index))) => {
+            tx.insert(&obj, index, ScalarValue::Null)?;
+            value.serialize(Serializer::new(tx, obj, index))?;
+        }
+        Some((obj, prop)) => {
+            value.serialize(Serializer::new(tx, obj, prop))?;
+        }
+        None => replace(tx, pointer, value)?,
+    }
+    Ok(())
+}
+
+fn remove<Tx: Transactable>(tx: &mut Tx, pointer: &str) -> Result<(), AutomergeSerdeError> {
+    let (obj, prop) =
+        resolve(tx, pointer, false)?.ok_or(AutomergeSerdeError::ExpectedRootObject)?;
+    if tx.get(&obj, prop.clone())?.is_none() {
+        return Err(AutomergeSerdeError::NotFound(pointer.to_owned()));
+    }
+    tx.delete(&obj, prop)?;
+    Ok(())
+}
+
+/// Reconcile `value` into the existing value at `pointer`, so that only the differences are
+/// written.
+fn replace<Tx: Transactable>(
+    tx: &mut Tx,
+    pointer: &str,
+    value: &serde_json::Value,
+) -> Result<(), AutomergeSerdeError> {
+    match resolve(tx, pointer, false)? {
+        Some((obj, prop)) => {
+            if tx.get(&obj, prop.clone())?.is_none() {
+                return Err(AutomergeSerdeError::NotFound(pointer.to_owned()));
+            }
+            value.serialize(Serializer::new(tx, obj, prop).with_reconcile(true))?;
+        }
+        None if value.is_object() => {
+            value.serialize(Serializer::new_object(tx, ObjId::Root).with_reconcile(true))?;
+        }
+        None => return Err(AutomergeSerdeError::ExpectedRootObject),
+    }
+    Ok(())
+}
+
+/// Read the value at `pointer` with its automerge types intact.
+fn read_node<Tx: Transactable>(tx: &Tx, pointer: &str) -> Result<Node, AutomergeSerdeError> {
+    let (value, obj) = match resolve(tx, pointer, false)? {
+        Some((obj, prop)) => tx
+            .get(&obj, prop)?
+            .ok_or_else(|| AutomergeSerdeError::NotFound(pointer.to_owned()))?,
+        None => (Value::Object(ObjType::Map), ObjId::Root),
+    };
+    Ok(Node::read(tx, value, &obj, None)?)
+}
+
+/// Write `node` at `pointer` the way `add` writes a JSON value.
+fn add_node<Tx: Transactable>(
+    tx: &mut Tx,
+    pointer: &str,
+    node: &Node,
+) -> Result<(), AutomergeSerdeError> {
+    match (resolve(tx, pointer, true)?, node) {
+        (Some((obj, prop @ Prop::Seq(_))), node) => node.write(tx, &obj, prop, true)?,
+        (Some((obj, prop)), node) => node.write(tx, &obj, prop, false)?,
+        (None, Node::Map(_)) => {
+            for key in tx.keys(ObjId::Root).collect::<Vec<_>>() {
+                tx.delete(ObjId::Root, key.as_str())?;
+            }
+            node.write_contents(tx, &ObjId::Root)?;
+        }
+        (None, _) => return Err(AutomergeSerdeError::ExpectedRootObject),
+    }
+    Ok(())
+}
+
+/// Apply a JSON Patch to the document in `tx`.
+///
+/// `add` and `remove` become inserts and deletes, while `replace` only writes the parts of the
+/// value that changed. `move` and `copy` write a copy of the automerge value, so text keeps its
+/// marks and counters stay counters, but the copy is a new object that concurrent edits to the
+/// original don't reach. Stops at the first operation that fails, leaving the operations before it
+/// applied; roll back the transaction to discard them.
+pub fn apply_json_patch<Tx: Transactable>(
+    tx: &mut Tx,
+    ops: &[JsonPatchOp],
+) -> Result<(), AutomergeSerdeError> {
+    for op in ops {
+        match op {
+            JsonPatchOp::Add { path, value } => add(tx, path, value)?,
+            JsonPatchOp::Remove { path } => remove(tx, path)?,
+            JsonPatchOp::Replace { path, value } => replace(tx, path, value)?,
+            JsonPatchOp::Move { from, path } => {
+                let node = read_node(tx, from)?;
+                remove(tx, from)?;
+                add_node(tx, path, &node)?;
+            }
+            JsonPatchOp::Copy { from, path } => {
+                let node = read_node(tx, from)?;
+                add_node(tx, path, &node)?;
+            }
+            JsonPatchOp::Test { path, value } => {
+                if get_json(tx, path)? != *value {
+                    return Err(AutomergeSerdeError::TestFailed(path.clone()));
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Remove all `null` members from objects in `value`, which is what merging it into an empty
+/// object does.
+fn strip_nulls(value: &serde_json::Value) -> serde_json::Value {
+    match value {
+        serde_json::Value::Object(members) => members
+            .iter()
+            .filter(|(_, v)| !v.is_null())
+            .map(|(k, v)| (k.clone(), strip_nulls(v)))
+            .collect(),
+        value => value.clone(),
+    }
+}
+
+fn merge_into<Tx: Transactable>(
+    tx: &mut Tx,
+    obj: &ObjId,
+    patch: &serde_json::Map<String, serde_json::Value>,
+) -> Result<(), AutomergeSerdeError> {
+    for (key, value) in patch {
+        let current = tx.get(obj, key.as_str())?;
+        match (value, current) {
+            (serde_json::Value::Null, Some(_)) => tx.delete(obj, key.as_str())?,
+            (serde_json::Value::Null, None) => {}
+            (
+                serde_json::Value::Object(members),
+                Some((Value::Object(ObjType::Map | ObjType::Table), child)),
+            ) => merge_into(tx, &child, members)?,
+            (value, _) => {
+                strip_nulls(value).serialize(
+                    Serializer::new(tx, obj.clone(), key.as_str()).with_reconcile(true),
+                )?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Apply a JSON Merge Patch to the root of the document in `tx`.
+///
+/// Objects in the patch are merged into existing maps member by member, `null` members delete
+/// keys and all other values are reconciled into the document.
+pub fn apply_merge_patch<Tx: Transactable>(
+    tx: &mut Tx,
+    patch: &serde_json::Value,
+) -> Result<(), AutomergeSerdeError> {
+    match patch {
+        serde_json::Value::Object(members) => merge_into(tx, &ObjId::Root, members),
+        _ => Err(AutomergeSerdeError::ExpectedRootObject),
+    }
+}
@@ -1,13 +1,51 @@
 #![doc = include_str!("../README.md")]
 
+pub mod attributed;
+pub mod blame;
+pub mod branch;
 pub mod de;
+pub mod diff;
+pub mod doc;
+pub mod handle;
+pub mod history;
+pub mod invariant;
+#[cfg(feature = "json-patch")]
+pub mod json_patch;
+pub mod merge;
+pub mod observe;
+pub mod patch;
+pub mod repair;
+#[cfg(feature = "repo")]
+pub mod repo;
 pub mod ser;
+pub mod session;
+pub mod shared;
+pub mod storage;
+pub mod text;
+#[cfg(feature = "tokio")]
+pub mod transport;
+pub mod undo;
+mod value;
 
+pub use attributed::Attributed;
 pub use automerge::*;
+pub use branch::Branches;
 pub use de::Deserializer;
+pub use diff::diff_typed;
+pub use doc::TypedDoc;
+pub use handle::{ListHandle, MapHandle, TextHandle};
+pub use observe::Observers;
+pub use repair::repair;
 pub use ser::Serializer;
+pub use session::SyncSession;
+pub use shared::SharedDoc;
+pub use text::{BlockText, RichText};
+pub use undo::UndoManager;
 
+/// Variants that only exist with some of the crate features enabled are gated behind them, so
+/// matches have to include a wildcard arm.
 #[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
 pub enum AutomergeSerdeError {
     #[error(transparent)]
     Serialize(#[from] ser::Error),
@@ -15,6 +53,46 @@ pub enum AutomergeSerdeError {
     Deserialize(#[from] de::Error),
     #[error(transparent)]
     Automerge(#[from] AutomergeError),
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error(transparent)]
+    Sync(#[from] sync::ReadMessageError),
+    /// See [`merge::ValidatedMerge`].
+    #[error(transparent)]
+    Rejected(Box<merge::Rejection>),
+    #[error("branch `{0}` already exists")]
+    BranchExists(String),
+    #[error("branch `{0}` does not exist")]
+    BranchNotFound(String),
+    #[error("invalid JSON pointer `{0}`")]
+    InvalidPointer(String),
+    #[error("no value at `{0}`")]
+    NotFound(String),
+    #[error("the value at `{0}` is not equal to the tested value")]
+    TestFailed(String),
+    #[error("the root of the document can only be replaced by an object")]
+    ExpectedRootObject,
+    #[error("invalid document id `{0}`")]
+    InvalidDocumentId(String),
+    #[cfg(feature = "repo")]
+    #[error("failed to encode message")]
+    Encode(#[source] ciborium::ser::Error<std::io::Error>),
+    #[cfg(feature = "repo")]
+    #[error("failed to decode message")]
+    Decode(#[source] ciborium::de::Error<std::io::Error>),
+    /// See [`transport::MAX_FRAME_LEN`].
+    #[cfg(feature = "tokio")]
+    #[error("a frame of {0} bytes is larger than `transport::MAX_FRAME_LEN`")]
+    FrameTooLarge(usize),
+    #[cfg(feature = "tokio")]
+    #[error("the sync loop is no longer running")]
+    Closed,
+}
+
+impl From<merge::Rejection> for AutomergeSerdeError {
+    fn from(rejection: merge::Rejection) -> Self {
+        Self::Rejected(Box::new(rejection))
+    }
 }
 
 pub trait AutomergeSetExtension {
@@ -25,6 +103,17 @@ pub trait AutomergeSetExtension {
         value: S,
     ) -> Result<ObjId, AutomergeSerdeError>;
 }
+/// Reconciling writes, see [`Serializer::with_reconcile()`].
+pub trait AutomergeReconcileExtension {
+    /// Like [`set_value()`][AutomergeSetExtension::set_value()], but only writes the parts of
+    /// `value` that differ from the document.
+    fn reconcile_value<S: serde::Serialize, P: Into<Prop>>(
+        &mut self,
+        obj: ObjId,
+        prop: P,
+        value: S,
+    ) -> Result<ObjId, AutomergeSerdeError>;
+}
 pub trait AutomergeGetExtension {
     fn get_value<'de, S: serde::Deserialize<'de>, P: Into<Prop>>(
         &self,
@@ -47,6 +136,20 @@ impl<'a> AutomergeSetExtension for transaction::Transaction<'a> {
     }
 }
 
+impl<'a> AutomergeReconcileExtension for transaction::Transaction<'a> {
+    fn reconcile_value<S: serde::Serialize, P: Into<Prop>>(
+        &mut self,
+        obj: ObjId,
+        prop: P,
+        value: S,
+    ) -> Result<ObjId, AutomergeSerdeError> {
+        value
+            .serialize(Serializer::new(self, obj, prop).with_reconcile(true))
+            .map(|(_, id)| id)
+            .map_err(Into::into)
+    }
+}
+
 impl AutomergeSetExtension for AutoCommit {
     fn set_value<S: serde::Serialize, P: Into<Prop>>(
         &mut self,
@@ -61,6 +164,20 @@ impl AutomergeSetExtension for AutoCommit {
     }
 }
 
+impl AutomergeReconcileExtension for AutoCommit {
+    fn reconcile_value<S: serde::Serialize, P: Into<Prop>>(
+        &mut self,
+        obj: ObjId,
+        prop: P,
+        value: S,
+    ) -> Result<ObjId, AutomergeSerdeError> {
+        value
+            .serialize(Serializer::new(self, obj, prop).with_reconcile(true))
+            .map(|(_, id)| id)
+            .map_err(Into::into)
+    }
+}
+
 impl AutomergeSetExtension for Automerge {
     fn set_value<S: serde::Serialize, P: Into<Prop>>(
         &mut self,
@@ -75,6 +192,20 @@ impl AutomergeSetExtension for Automerge {
     }
 }
 
+impl AutomergeReconcileExtension for Automerge {
+    fn reconcile_value<S: serde::Serialize, P: Into<Prop>>(
+        &mut self,
+        obj: ObjId,
+        prop: P,
+        value: S,
+    ) -> Result<ObjId, AutomergeSerdeError> {
+        let mut transaction = self.transaction();
+        let id = transaction.reconcile_value(obj, prop, value)?;
+        transaction.commit();
+        Ok(id)
+    }
+}
+
 impl AutomergeGetExtension for Automerge {
     fn get_value<'de, S: serde::Deserialize<'de>, P: Into<Prop>>(
         &self,
@@ -89,5 +220,19 @@ impl AutomergeGetExtension for Automerge {
     }
 }
 
+impl AutomergeGetExtension for AutoCommit {
+    fn get_value<'de, S: serde::Deserialize<'de>, P: Into<Prop>>(
+        &self,
+        obj: ObjId,
+        prop: P,
+    ) -> Result<Option<S>, AutomergeSerdeError> {
+        self.get(obj, prop)?
+            .map(|(v, id)| Deserializer::new_found(self, v, id))
+            .map(|d| S::deserialize(d))
+            .transpose()
+            .map_err(Into::into)
+    }
+}
+
 pub trait AutomergeExtension: AutomergeSetExtension + AutomergeGetExtension {}
 impl<T: AutomergeSetExtension + AutomergeGetExtension> AutomergeExtension for T {}
@@ -0,0 +1,185 @@
+use crate::{
+    de,
+    diff::{diff_typed, field_changes, FieldChange, FieldValue, TypedDiff},
+    AutomergeSerdeError, Deserializer, TypedDoc,
+};
+use automerge::{patches::TextRepresentation, Automerge, ChangeHash, PatchAction, Prop, ReadDoc};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+/// A property that has multiple concurrent values after a merge.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Conflict {
+    pub path: Vec<Prop>,
+    /// All values, starting with the one that is read from the document.
+    pub values: Vec<FieldValue>,
+}
+
+/// The effect that merging changes would have on a document.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MergePreview<T> {
+    /// The value after the merge.
+    pub value: T,
+    pub diff: TypedDiff<T>,
+    /// The conflicts that the merge introduces.
+    pub conflicts: Vec<Conflict>,
+}
+
+/// Find the conflicts that the changes between the heads of `before` and `after` introduce.
+fn conflicts(before: &Automerge, after: &Automerge) -> Result<Vec<Conflict>, AutomergeSerdeError> {
+    let patches = after.diff(
+        &before.get_heads(),
+        &after.get_heads(),
+        TextRepresentation::String(after.text_encoding()),
+    );
+    let mut conflicts = Vec::new();
+    for patch in patches {
+        let prop = match patch.action {
+            PatchAction::PutMap {
+                key,
+                conflict: true,
+                ..
+            } => Prop::Map(key),
+            PatchAction::PutSeq {
+                index,
+                conflict: true,
+                ..
+            } => Prop::Seq(index),
+            PatchAction::Conflict { prop } => prop,
+            _ => continue,
+        };
+        let values = after
+            .get_all(&patch.obj, prop.clone())?
+            .into_iter()
+            // The value that wins comes last
+            .rev()
+            .map(|value| FieldValue::deserialize(Deserializer::new(after, Some(value))))
+            .collect::<Result<_, _>>()?;
+        let mut path = patch.path.into_iter().map(|(_, p)| p).collect::<Vec<_>>();
+        path.push(prop);
+        if !conflicts.iter().any(|c: &Conflict| c.path == path) {
+            conflicts.push(Conflict { path, values });
+        }
+    }
+    Ok(conflicts)
+}
+
+/// Show what loading `incoming`, which is the output of `save()` or `save_incremental()`, into
+/// `doc` would do, without changing `doc`.
+pub fn preview_merge<T: DeserializeOwned>(
+    doc: &Automerge,
+    incoming: &[u8],
+) -> Result<MergePreview<T>, AutomergeSerdeError> {
+    let mut merged = doc.fork();
+    merged.load_incremental(incoming)?;
+    Ok(MergePreview {
+        value: T::deserialize(Deserializer::new_root(&merged))?,
+        diff: diff_typed(&merged, &doc.get_heads(), &merged.get_heads())?,
+        conflicts: conflicts(doc, &merged)?,
+    })
+}
+
+/// Why [`ValidatedMerge`] rejected incoming changes, returned as
+/// [`AutomergeSerdeError::Rejected`]. The document is left untouched.
+#[derive(Debug, thiserror::Error)]
+pub enum Rejection {
+    #[error("the merged document no longer deserializes: {error}")]
+    Schema {
+        error: de::Error,
+        /// The values that the incoming changes would change.
+        changes: Vec<FieldChange>,
+    },
+    #[error("the merged value is invalid: {message}")]
+    Invalid {
+        message: String,
+        /// The values that the incoming changes would change.
+        changes: Vec<FieldChange>,
+    },
+}
+
+type Validator<T> = Box<dyn Fn(&T) -> Result<(), String>>;
+
+/// Merges incoming changes into a [`TypedDoc`] only if the result still deserializes as `T` and
+/// passes all validators.
+///
+/// The changes are applied to a fork of the document first, so that a peer that sends malformed
+/// changes can't leave the document in a state that no longer deserializes.
+pub struct ValidatedMerge<T> {
+    validators: Vec<Validator<T>>,
+}
+
+impl<T> Default for ValidatedMerge<T> {
+    fn default() -> Self {
+        Self {
+            validators: Vec::new(),
+        }
+    }
+}
+
+impl<T: Serialize + DeserializeOwned> ValidatedMerge<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Also reject changes for which `validator` returns an error message.
+    pub fn with_validator(
+        mut self,
+        validator: impl Fn(&T) -> Result<(), String> + 'static,
+    ) -> Self {
+        self.validators.push(Box::new(validator));
+        self
+    }
+
+    /// Check the merged document and return its value.
+    fn check(&self, merged: &Automerge, before: &[ChangeHash]) -> Result<T, AutomergeSerdeError> {
+        let changes = || field_changes(merged, before, &merged.get_heads());
+        let value = match T::deserialize(Deserializer::new_root(merged)) {
+            Ok(value) => value,
+            Err(error) => {
+                let changes = changes()?;
+                return Err(Rejection::Schema { error, changes }.into());
+            }
+        };
+        for validator in &self.validators {
+            if let Err(message) = validator(&value) {
+                let changes = changes()?;
+                return Err(Rejection::Invalid { message, changes }.into());
+            }
+        }
+        Ok(value)
+    }
+
+    /// Load `incoming`, the output of `save()` or `save_incremental()`, into `doc` if the result is
+    /// valid, and return the new value.
+    pub fn load_incremental(
+        &self,
+        doc: &mut TypedDoc<T>,
+        incoming: &[u8],
+    ) -> Result<T, AutomergeSerdeError> {
+        let current = doc.doc_mut().document();
+        let before = current.get_heads();
+        let mut merged = current.fork();
+        merged.load_incremental(incoming)?;
+        let value = self.check(&merged, &before)?;
+        doc.load_incremental(incoming)?;
+        Ok(value)
+    }
+    /// Merge the changes of `other` that `doc` doesn't have yet if the result is valid, and return
+    /// the new value.
+    pub fn merge(
+        &self,
+        doc: &mut TypedDoc<T>,
+        other: &mut TypedDoc<T>,
+    ) -> Result<T, AutomergeSerdeError> {
+        let current = doc.doc_mut().document();
+        let changes = current
+            .get_changes_added(other.doc_mut().document())
+            .into_iter()
+            .cloned()
+            .collect::<Vec<_>>();
+        let before = current.get_heads();
+        let mut merged = current.fork();
+        merged.apply_changes(changes.clone())?;
+        let value = self.check(&merged, &before)?;
+        doc.doc_mut().apply_changes(changes)?;
+        Ok(value)
+    }
+}
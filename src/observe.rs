@@ -0,0 +1,119 @@
+use crate::{
+    diff::get_path,
+    patch::{overlaps, targets},
+    AutomergeSerdeError, Deserializer,
+};
+use automerge::{patches::TextRepresentation, Automerge, ChangeHash, Patch, Prop};
+use serde::de::DeserializeOwned;
+
+type Notify = Box<dyn FnMut(&Automerge) -> Result<(), AutomergeSerdeError>>;
+
+/// Identifies a callback registered with [`Observers::subscribe()`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SubscriptionId(u64);
+
+struct Subscription {
+    id: SubscriptionId,
+    path: Vec<Prop>,
+    notify: Notify,
+}
+
+/// The subscriptions whose value could not be read while notifying them, with the reason.
+pub type NotifyErrors = Vec<(SubscriptionId, AutomergeSerdeError)>;
+
+/// A registry of callbacks that are interested in the value at one path of a document.
+///
+/// [`Observers::update()`] inspects the patches since the last update and only calls the callbacks
+/// whose path was changed, with the value at that path deserialized again.
+#[derive(Default)]
+pub struct Observers {
+    subscriptions: Vec<Subscription>,
+    next_id: u64,
+    heads: Vec<ChangeHash>,
+}
+
+impl Observers {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Start observing from `heads`, so that the first [`Observers::update()`] only reports
+    /// changes made after them.
+    pub fn with_heads(mut self, heads: Vec<ChangeHash>) -> Self {
+        self.heads = heads;
+        self
+    }
+    /// The heads up to which callbacks have been notified.
+    pub fn heads(&self) -> &[ChangeHash] {
+        &self.heads
+    }
+
+    /// Call `callback` with the value of type `T` at `path` whenever something at, inside of or
+    /// above `path` changed. The value is [`None`] if `path` no longer exists.
+    pub fn subscribe<T: DeserializeOwned + 'static>(
+        &mut self,
+        path: Vec<Prop>,
+        mut callback: impl FnMut(Option<T>) + 'static,
+    ) -> SubscriptionId {
+        let id = SubscriptionId(self.next_id);
+        self.next_id += 1;
+        let read_path = path.clone();
+        let notify: Notify = Box::new(move |doc| {
+            let value = get_path(doc, &read_path, None)?
+                .map(|node| T::deserialize(Deserializer::new(doc, Some(node))))
+                .transpose()?;
+            callback(value);
+            Ok(())
+        });
+        self.subscriptions.push(Subscription { id, path, notify });
+        id
+    }
+    /// Remove a callback, returns `false` if it was not registered.
+    pub fn unsubscribe(&mut self, id: SubscriptionId) -> bool {
+        let len = self.subscriptions.len();
+        self.subscriptions.retain(|s| s.id != id);
+        self.subscriptions.len() != len
+    }
+    pub fn len(&self) -> usize {
+        self.subscriptions.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.subscriptions.is_empty()
+    }
+
+    /// Notify the callbacks affected by all changes to `doc` since the last update, for instance
+    /// after a local transaction or a merge.
+    ///
+    /// See [`Observers::notify()`] for what happens if a value can't be read.
+    pub fn update(&mut self, doc: &Automerge) -> Result<(), NotifyErrors> {
+        let heads = doc.get_heads();
+        let patches = doc.diff(
+            &self.heads,
+            &heads,
+            TextRepresentation::String(doc.text_encoding()),
+        );
+        self.heads = heads;
+        self.notify(doc, patches)
+    }
+    /// Notify the callbacks affected by `patches`, which lead up to the current state of `doc`.
+    ///
+    /// A value that can't be deserialized doesn't stop the other callbacks from being notified,
+    /// the errors of all callbacks that were skipped are returned together.
+    pub fn notify(
+        &mut self,
+        doc: &Automerge,
+        patches: impl IntoIterator<Item = Patch>,
+    ) -> Result<(), NotifyErrors> {
+        let targets = targets(patches);
+        let errors = self
+            .subscriptions
+            .iter_mut()
+            .filter(|s| targets.iter().any(|t| overlaps(t, &s.path)))
+            .filter_map(|s| (s.notify)(doc).err().map(|e| (s.id, e)))
+            .collect::<NotifyErrors>();
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
@@ -0,0 +1,248 @@
+use crate::{
+    de::KeyDeserializer, Attributed, AutomergeSerdeError, BlockText, Deserializer, RichText,
+};
+use automerge::{ObjId, ObjType, Patch, PatchAction, Prop, ReadDoc, Value};
+use serde::de::DeserializeOwned;
+use std::{
+    collections::{BTreeMap, HashMap},
+    hash::{BuildHasher, Hash},
+};
+
+/// Values that can be brought up to date with a document without deserializing them again as a
+/// whole.
+///
+/// [`ApplyPatch::reload()`] is called with the `path` to the part of the value that changed.
+/// Containers follow the path into their elements, everything else falls back to deserializing
+/// itself again through [`Deserializer::new()`]. Structs can use [`impl_apply_patch!`][crate::impl_apply_patch] to follow
+/// the path into their fields, or an empty `impl ApplyPatch for T {}` to always be re-read in full.
+pub trait ApplyPatch: DeserializeOwned {
+    /// Re-read the part of `self` at `path` from `doc`, where `self` was deserialized from `node`.
+    fn reload<'a, Rx: ReadDoc>(
+        &mut self,
+        doc: &'a Rx,
+        node: Option<(Value<'a>, ObjId)>,
+        path: &[Prop],
+    ) -> Result<(), AutomergeSerdeError> {
+        let _ = path;
+        reload_all(self, doc, node)
+    }
+}
+
+/// Deserialize all of `value` again from `node`.
+pub fn reload_all<'a, T: DeserializeOwned, Rx: ReadDoc>(
+    value: &mut T,
+    doc: &'a Rx,
+    node: Option<(Value<'a>, ObjId)>,
+) -> Result<(), AutomergeSerdeError> {
+    *value = T::deserialize(Deserializer::new(doc, node))?;
+    Ok(())
+}
+
+/// The path below the root to the part of the document that `patch` invalidated.
+fn target(patch: Patch) -> Vec<Prop> {
+    let mut path = patch.path.into_iter().map(|(_, p)| p).collect::<Vec<_>>();
+    match patch.action {
+        PatchAction::PutMap { key, .. } | PatchAction::DeleteMap { key } => {
+            path.push(Prop::Map(key))
+        }
+        PatchAction::PutSeq { index, .. } => path.push(Prop::Seq(index)),
+        PatchAction::Increment { prop, .. } | PatchAction::Conflict { prop } => path.push(prop),
+        // Inserting into or deleting from a sequence shifts the indices of all elements after it,
+        // and text is always read as a whole, so these invalidate the entire object
+        PatchAction::Insert { .. }
+        | PatchAction::DeleteSeq { .. }
+        | PatchAction::SpliceText { .. }
+        | PatchAction::Mark { .. } => {}
+    }
+    path
+}
+
+/// The paths below the root that `patches` invalidated, without paths that are inside of another
+/// path in the list.
+pub(crate) fn targets(patches: impl IntoIterator<Item = Patch>) -> Vec<Vec<Prop>> {
+    let mut targets = patches.into_iter().map(target).collect::<Vec<_>>();
+    // After sorting, any path that is inside of another path comes right after it
+    targets.sort();
+    targets.dedup_by(|path, parent| path.starts_with(parent));
+    targets
+}
+
+/// Whether one of `a` and `b` is inside of the other, so that a change to one affects the other.
+pub(crate) fn overlaps(a: &[Prop], b: &[Prop]) -> bool {
+    a.starts_with(b) || b.starts_with(a)
+}
+
+/// Update `value`, which was deserialized from the root of `doc`, with `patches` that lead up to
+/// the current state of `doc`.
+///
+/// Only the parts of `value` touched by the patches are deserialized again, all values are read
+/// from the current state of `doc`.
+pub fn apply_patches<T: ApplyPatch, Rx: ReadDoc>(
+    value: &mut T,
+    doc: &Rx,
+    patches: impl IntoIterator<Item = Patch>,
+) -> Result<(), AutomergeSerdeError> {
+    for path in targets(patches) {
+        value.reload(doc, Some((ObjType::Map.into(), ObjId::Root)), &path)?;
+    }
+    Ok(())
+}
+
+/// Implement [`ApplyPatch`] for a struct, following the path into the listed fields.
+///
+/// Fields are matched by the key they are serialized with, which defaults to the field name.
+/// Renamed fields give their key after `as`:
+///
+/// ```
+/// use serde::{Deserialize, Serialize};
+/// use serde_automerge::impl_apply_patch;
+///
+/// #[derive(Deserialize, Serialize)]
+/// struct Note {
+///     title: String,
+///     #[serde(rename = "noteBody")]
+///     body: String,
+/// }
+/// impl_apply_patch!(Note { title, body as "noteBody" });
+/// ```
+#[macro_export]
+macro_rules! impl_apply_patch {
+    ($type:ty { $($field:ident $(as $key:literal)?),* $(,)? }) => {
+        impl $crate::patch::ApplyPatch for $type {
+            fn reload<'a, Rx: $crate::ReadDoc>(
+                &mut self,
+                doc: &'a Rx,
+                node: Option<($crate::Value<'a>, $crate::ObjId)>,
+                path: &[$crate::Prop],
+            ) -> Result<(), $crate::AutomergeSerdeError> {
+                if let (Some((_, id)), [$crate::Prop::Map(key), rest @ ..]) = (&node, path) {
+                    $(
+                        if key == $crate::impl_apply_patch!(@key $field $($key)?) {
+                            let child = $crate::ReadDoc::get(doc, id, key.as_str())?;
+                            return self.$field.reload(doc, child, rest);
+                        }
+                    )*
+                }
+                $crate::patch::reload_all(self, doc, node)
+            }
+        }
+    };
+    (@key $field:ident) => {
+        stringify!($field)
+    };
+    (@key $field:ident $key:literal) => {
+        $key
+    };
+}
+
+macro_rules! impl_reload_all {
+    ($($type:ty),*) => {
+        $(impl ApplyPatch for $type {})*
+    };
+}
+
+impl_reload_all!(
+    (),
+    bool,
+    i8,
+    i16,
+    i32,
+    i64,
+    i128,
+    u8,
+    u16,
+    u32,
+    u64,
+    u128,
+    f32,
+    f64,
+    char,
+    String,
+    RichText,
+    BlockText
+);
+
+impl<T: ApplyPatch> ApplyPatch for Option<T> {
+    fn reload<'a, Rx: ReadDoc>(
+        &mut self,
+        doc: &'a Rx,
+        node: Option<(Value<'a>, ObjId)>,
+        path: &[Prop],
+    ) -> Result<(), AutomergeSerdeError> {
+        match (self, &node) {
+            (Some(value), Some((v, _))) if !path.is_empty() && v.is_object() => {
+                value.reload(doc, node, path)
+            }
+            (this, _) => reload_all(this, doc, node),
+        }
+    }
+}
+
+impl<T: ApplyPatch> ApplyPatch for Box<T> {
+    fn reload<'a, Rx: ReadDoc>(
+        &mut self,
+        doc: &'a Rx,
+        node: Option<(Value<'a>, ObjId)>,
+        path: &[Prop],
+    ) -> Result<(), AutomergeSerdeError> {
+        T::reload(self, doc, node, path)
+    }
+}
+
+// Re-read in full so that the id of the operation is updated as well
+impl<T: DeserializeOwned> ApplyPatch for Attributed<T> {}
+
+impl<T: ApplyPatch> ApplyPatch for Vec<T> {
+    fn reload<'a, Rx: ReadDoc>(
+        &mut self,
+        doc: &'a Rx,
+        node: Option<(Value<'a>, ObjId)>,
+        path: &[Prop],
+    ) -> Result<(), AutomergeSerdeError> {
+        if let (Some((Value::Object(ObjType::List), id)), [Prop::Seq(index), rest @ ..]) =
+            (&node, path)
+        {
+            if let (Some(element), Some(child)) = (self.get_mut(*index), doc.get(id, *index)?) {
+                return element.reload(doc, Some(child), rest);
+            }
+        }
+        reload_all(self, doc, node)
+    }
+}
+
+macro_rules! impl_apply_patch_map {
+    ([$($generics:tt)*] $type:ty) => {
+        impl<$($generics)*> ApplyPatch for $type {
+            fn reload<'a, Rx: ReadDoc>(
+                &mut self,
+                doc: &'a Rx,
+                node: Option<(Value<'a>, ObjId)>,
+                path: &[Prop],
+            ) -> Result<(), AutomergeSerdeError> {
+                let (
+                    Some((Value::Object(ObjType::Map | ObjType::Table), id)),
+                    [Prop::Map(key), rest @ ..],
+                ) = (&node, path)
+                else {
+                    return reload_all(self, doc, node);
+                };
+                let k = K::deserialize(KeyDeserializer::new(key))?;
+                match (self.get_mut(&k), doc.get(id, key.as_str())?) {
+                    (_, None) => {
+                        self.remove(&k);
+                    }
+                    (Some(value), child) => value.reload(doc, child, rest)?,
+                    (None, child) => {
+                        self.insert(k, V::deserialize(Deserializer::new(doc, child))?);
+                    }
+                }
+                Ok(())
+            }
+        }
+    };
+}
+
+impl_apply_patch_map!([K: Ord + DeserializeOwned, V: ApplyPatch] BTreeMap<K, V>);
+impl_apply_patch_map!(
+    [K: Eq + Hash + DeserializeOwned, V: ApplyPatch, S: BuildHasher + Default] HashMap<K, V, S>
+);
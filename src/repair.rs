@@ -0,0 +1,58 @@
+use crate::{
+    de::{Found, Lenient},
+    diff::{get_path_in, FieldChange, FieldValue},
+    AutomergeSerdeError, Deserializer, Serializer,
+};
+use automerge::{transaction::Transactable, ObjId, Value};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+/// The outcome of [`repair()`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Repair<T> {
+    /// The value that the object now holds.
+    pub value: T,
+    /// The values below the object that were converted, replaced or removed.
+    pub changes: Vec<FieldChange>,
+}
+
+impl<T> Repair<T> {
+    pub fn is_clean(&self) -> bool {
+        self.changes.is_empty()
+    }
+}
+
+/// Coerce the object `obj` into a shape that deserializes as `T`.
+///
+/// The object is read leniently: scalars of the wrong type are converted where possible, e.g.
+/// `"42"` to `42`, and values that can't be converted, missing struct fields and lists or maps
+/// where a scalar is expected are replaced by the default of the type. Keys that are not a field
+/// of their struct are removed. The result is reconciled back into `obj`, so only the parts that
+/// changed are written. Enums and tuples are still read strictly.
+pub fn repair<T: Serialize + DeserializeOwned, Tx: Transactable>(
+    tx: &mut Tx,
+    obj: ObjId,
+) -> Result<Repair<T>, AutomergeSerdeError> {
+    let found = Found::default();
+    let value = {
+        let object = Value::Object(tx.object_type(&obj)?);
+        let deserializer = Deserializer::new_found(&*tx, object, obj.clone())
+            .with_lenient(Some(Lenient::new(&found)));
+        T::deserialize(deserializer)?
+    };
+    value.serialize(Serializer::new_object(tx, obj.clone()).with_reconcile(true))?;
+
+    let mut found = found.into_inner();
+    // Nothing below a value that was replaced as a whole is left to report on
+    found.sort_by(|(a, _), (b, _)| a.cmp(b));
+    found.dedup_by(|(path, _), (parent, _)| path.starts_with(parent));
+    let changes = found
+        .into_iter()
+        .map(|(path, old)| {
+            let new = get_path_in(&*tx, obj.clone(), &path, None)?
+                .map(|node| FieldValue::deserialize(Deserializer::new(&*tx, Some(node))))
+                .transpose()?;
+            Ok(FieldChange { path, old, new })
+        })
+        .collect::<Result<_, AutomergeSerdeError>>()?;
+    Ok(Repair { value, changes })
+}
@@ -0,0 +1,480 @@
+//! Interoperability with [automerge-repo](https://github.com/automerge/automerge-repo): its CBOR
+//! network messages, document ids and storage key layout, and a [`RepoPeer`] that speaks the
+//! protocol.
+
+use crate::{
+    storage::{chunk_hash, document_dir, snapshot_hash},
+    AutomergeSerdeError, Deserializer, Serializer,
+};
+use automerge::{
+    sync::{Message, State, SyncDoc},
+    AutoCommit, ChangeHash, ObjId,
+};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+    path::PathBuf,
+    str::FromStr,
+};
+
+/// The only protocol version that automerge-repo currently defines.
+pub const PROTOCOL_VERSION: &str = "1";
+
+const URL_PREFIX: &str = "automerge:";
+
+/// The id of a document, 16 random bytes that are shown as a base58check string.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct DocumentId([u8; 16]);
+
+impl DocumentId {
+    pub fn new() -> Self {
+        Self(*uuid::Uuid::new_v4().as_bytes())
+    }
+    pub fn from_bytes(bytes: [u8; 16]) -> Self {
+        Self(bytes)
+    }
+    pub fn as_bytes(&self) -> &[u8; 16] {
+        &self.0
+    }
+    /// The `automerge:` url of this document.
+    pub fn to_url(&self) -> String {
+        format!("{URL_PREFIX}{self}")
+    }
+    pub fn from_url(url: &str) -> Result<Self, AutomergeSerdeError> {
+        url.strip_prefix(URL_PREFIX)
+            .ok_or_else(|| AutomergeSerdeError::InvalidDocumentId(url.to_owned()))?
+            .parse()
+    }
+}
+
+impl Default for DocumentId {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Display for DocumentId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&bs58::encode(self.0).with_check().into_string())
+    }
+}
+
+impl FromStr for DocumentId {
+    type Err = AutomergeSerdeError;
+
+    fn from_str(s: &str) -> Result<Self, AutomergeSerdeError> {
+        let bytes = bs58::decode(s)
+            .with_check(None)
+            .into_vec()
+            .map_err(|_| AutomergeSerdeError::InvalidDocumentId(s.to_owned()))?;
+        let bytes = bytes
+            .try_into()
+            .map_err(|_| AutomergeSerdeError::InvalidDocumentId(s.to_owned()))?;
+        Ok(Self(bytes))
+    }
+}
+
+impl Serialize for DocumentId {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for DocumentId {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+/// The id that a peer announces itself with.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct PeerId(pub String);
+
+impl From<&str> for PeerId {
+    fn from(id: &str) -> Self {
+        Self(id.to_owned())
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PeerMetadata {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub storage_id: Option<String>,
+    #[serde(default)]
+    pub is_ephemeral: bool,
+}
+
+/// A message of the automerge-repo network protocol, encoded as a CBOR map.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(
+    tag = "type",
+    rename_all = "kebab-case",
+    rename_all_fields = "camelCase"
+)]
+pub enum RepoMessage {
+    Join {
+        sender_id: PeerId,
+        supported_protocol_versions: Vec<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        peer_metadata: Option<PeerMetadata>,
+    },
+    Peer {
+        sender_id: PeerId,
+        target_id: PeerId,
+        selected_protocol_version: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        peer_metadata: Option<PeerMetadata>,
+    },
+    Error {
+        message: String,
+    },
+    /// Sync data for a document that the sender has.
+    Sync {
+        sender_id: PeerId,
+        target_id: PeerId,
+        document_id: DocumentId,
+        #[serde(with = "serde_bytes")]
+        data: Vec<u8>,
+    },
+    /// Sync data for a document that the sender does not have yet.
+    Request {
+        sender_id: PeerId,
+        target_id: PeerId,
+        document_id: DocumentId,
+        #[serde(with = "serde_bytes")]
+        data: Vec<u8>,
+    },
+    DocUnavailable {
+        sender_id: PeerId,
+        target_id: PeerId,
+        document_id: DocumentId,
+    },
+}
+
+/// The `type` of every [`RepoMessage`] variant.
+const MESSAGE_TYPES: &[&str] = &[
+    "join",
+    "peer",
+    "error",
+    "sync",
+    "request",
+    "doc-unavailable",
+];
+
+impl RepoMessage {
+    pub fn encode(&self) -> Result<Vec<u8>, AutomergeSerdeError> {
+        let mut bytes = Vec::new();
+        ciborium::into_writer(self, &mut bytes).map_err(AutomergeSerdeError::Encode)?;
+        Ok(bytes)
+    }
+    /// Decode a message, or [`None`] if it is of a type that this crate does not handle, such as
+    /// the `ephemeral` and `remote-heads-changed` messages.
+    pub fn decode(bytes: &[u8]) -> Result<Option<Self>, AutomergeSerdeError> {
+        #[derive(Deserialize)]
+        struct Tagged {
+            r#type: String,
+        }
+        let Tagged { r#type } =
+            ciborium::from_reader(bytes).map_err(AutomergeSerdeError::Decode)?;
+        if !MESSAGE_TYPES.contains(&r#type.as_str()) {
+            return Ok(None);
+        }
+        ciborium::from_reader(bytes)
+            .map(Some)
+            .map_err(AutomergeSerdeError::Decode)
+    }
+}
+
+/// A key of the automerge-repo storage layout, which is also the layout of
+/// [`DocStore`][crate::storage::DocStore] when documents are named by their id.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum StorageKey {
+    /// A compacted document, keyed by the hash of its heads.
+    Snapshot {
+        document_id: DocumentId,
+        hash: String,
+    },
+    /// Changes saved on top of the snapshots, keyed by the hash of the chunk.
+    Incremental {
+        document_id: DocumentId,
+        hash: String,
+    },
+    /// The sync state with the peer that has this storage id.
+    SyncState {
+        document_id: DocumentId,
+        storage_id: String,
+    },
+}
+
+impl StorageKey {
+    pub fn snapshot(document_id: DocumentId, heads: &[ChangeHash]) -> Self {
+        Self::Snapshot {
+            document_id,
+            hash: snapshot_hash(heads),
+        }
+    }
+    pub fn incremental(document_id: DocumentId, chunk: &[u8]) -> Self {
+        Self::Incremental {
+            document_id,
+            hash: chunk_hash(chunk),
+        }
+    }
+
+    pub fn document_id(&self) -> DocumentId {
+        match self {
+            Self::Snapshot { document_id, .. }
+            | Self::Incremental { document_id, .. }
+            | Self::SyncState { document_id, .. } => *document_id,
+        }
+    }
+    /// The key as the array of strings used by automerge-repo storage adapters.
+    pub fn to_parts(&self) -> Vec<String> {
+        let (kind, hash) = match self {
+            Self::Snapshot { hash, .. } => ("snapshot", hash),
+            Self::Incremental { hash, .. } => ("incremental", hash),
+            Self::SyncState { storage_id, .. } => ("sync-state", storage_id),
+        };
+        vec![
+            self.document_id().to_string(),
+            kind.to_owned(),
+            hash.clone(),
+        ]
+    }
+    pub fn from_parts<S: AsRef<str>>(parts: &[S]) -> Option<Self> {
+        let [document_id, kind, hash] = parts else {
+            return None;
+        };
+        let document_id = document_id.as_ref().parse().ok()?;
+        let hash = hash.as_ref().to_owned();
+        Some(match kind.as_ref() {
+            "snapshot" => Self::Snapshot { document_id, hash },
+            "incremental" => Self::Incremental { document_id, hash },
+            "sync-state" => Self::SyncState {
+                document_id,
+                storage_id: hash,
+            },
+            _ => return None,
+        })
+    }
+    /// The path relative to the storage directory, as laid out by the NodeFS storage adapter:
+    /// the first two characters of the document id, the rest of it and then the other parts.
+    pub fn to_path(&self) -> PathBuf {
+        let parts = self.to_parts();
+        let mut path = document_dir(&parts[0]).split('/').collect::<PathBuf>();
+        path.extend(&parts[1..]);
+        path
+    }
+}
+
+/// A peer of an automerge-repo network that holds a set of documents.
+///
+/// [`RepoPeer::receive()`] handles a message from another peer and returns the messages to send
+/// in response, moving the bytes between peers is up to the caller.
+pub struct RepoPeer {
+    peer_id: PeerId,
+    metadata: Option<PeerMetadata>,
+    peers: HashSet<PeerId>,
+    docs: HashMap<DocumentId, AutoCommit>,
+    /// Documents that were requested from other peers, until the first sync data for them arrives.
+    pending: HashMap<DocumentId, AutoCommit>,
+    states: HashMap<(PeerId, DocumentId), State>,
+}
+
+impl RepoPeer {
+    pub fn new(peer_id: PeerId) -> Self {
+        Self {
+            peer_id,
+            metadata: None,
+            peers: HashSet::new(),
+            docs: HashMap::new(),
+            pending: HashMap::new(),
+            states: HashMap::new(),
+        }
+    }
+    pub fn with_metadata(mut self, metadata: PeerMetadata) -> Self {
+        self.metadata = Some(metadata);
+        self
+    }
+    pub fn peer_id(&self) -> &PeerId {
+        &self.peer_id
+    }
+    /// The peers that completed the handshake.
+    pub fn peers(&self) -> impl Iterator<Item = &PeerId> {
+        self.peers.iter()
+    }
+    /// The first message to send after connecting to another peer.
+    pub fn join(&self) -> RepoMessage {
+        RepoMessage::Join {
+            sender_id: self.peer_id.clone(),
+            supported_protocol_versions: vec![PROTOCOL_VERSION.to_owned()],
+            peer_metadata: self.metadata.clone(),
+        }
+    }
+
+    pub fn document(&self, id: &DocumentId) -> Option<&AutoCommit> {
+        self.docs.get(id)
+    }
+    pub fn insert_document(&mut self, id: DocumentId, doc: AutoCommit) {
+        self.pending.remove(&id);
+        self.docs.insert(id, doc);
+    }
+    /// Store `value` in a new document with a random id.
+    pub fn create<T: Serialize>(&mut self, value: &T) -> Result<DocumentId, AutomergeSerdeError> {
+        let id = DocumentId::new();
+        self.docs.insert(id, AutoCommit::new());
+        self.write(&id, value)?;
+        Ok(id)
+    }
+    /// Read the value at the root of a document, or [`None`] if this peer does not have it.
+    pub fn read<T: DeserializeOwned>(
+        &self,
+        id: &DocumentId,
+    ) -> Result<Option<T>, AutomergeSerdeError> {
+        self.docs
+            .get(id)
+            .map(|doc| T::deserialize(Deserializer::new_root(doc)))
+            .transpose()
+            .map_err(Into::into)
+    }
+    /// Reconcile `value` into a document and return the sync messages that share the change with
+    /// the peers that sync it.
+    ///
+    /// Strings that are stored as text objects, as automerge-repo in JavaScript does, stay text
+    /// objects and are updated in place, so that concurrent edits to them merge.
+    pub fn write<T: Serialize>(
+        &mut self,
+        id: &DocumentId,
+        value: &T,
+    ) -> Result<Vec<RepoMessage>, AutomergeSerdeError> {
+        let pending = &mut self.pending;
+        let doc = self
+            .docs
+            .entry(*id)
+            .or_insert_with(|| pending.remove(id).unwrap_or_default());
+        let result = value.serialize(Serializer::new_object(doc, ObjId::Root).with_reconcile(true));
+        if let Err(e) = result {
+            doc.rollback();
+            return Err(e.into());
+        }
+        doc.commit();
+        Ok(self.sync_messages(id))
+    }
+
+    /// Ask `target` for a document that this peer does not have yet.
+    ///
+    /// The document is only available from [`RepoPeer::read()`] and [`RepoPeer::document()`] once
+    /// a peer sent data for it, and other peers that request it in the meantime are told that it
+    /// is unavailable.
+    pub fn request(&mut self, id: DocumentId, target: PeerId) -> Option<RepoMessage> {
+        let doc = match self.docs.get_mut(&id) {
+            Some(doc) => doc,
+            None => self.pending.entry(id).or_default(),
+        };
+        let state = self.states.entry((target.clone(), id)).or_default();
+        let message = doc.sync().generate_sync_message(state)?;
+        Some(RepoMessage::Request {
+            sender_id: self.peer_id.clone(),
+            target_id: target,
+            document_id: id,
+            data: message.encode(),
+        })
+    }
+    /// Sync messages for all peers that sync the document `id`.
+    pub fn sync_messages(&mut self, id: &DocumentId) -> Vec<RepoMessage> {
+        let Some(doc) = self.docs.get_mut(id) else {
+            return Vec::new();
+        };
+        let mut messages = Vec::new();
+        for ((peer, document_id), state) in &mut self.states {
+            if document_id != id {
+                continue;
+            }
+            if let Some(message) = doc.sync().generate_sync_message(state) {
+                messages.push(RepoMessage::Sync {
+                    sender_id: self.peer_id.clone(),
+                    target_id: peer.clone(),
+                    document_id: *id,
+                    data: message.encode(),
+                });
+            }
+        }
+        messages
+    }
+
+    /// Handle a message from another peer and return the messages to send in response.
+    pub fn receive(
+        &mut self,
+        message: RepoMessage,
+    ) -> Result<Vec<RepoMessage>, AutomergeSerdeError> {
+        match message {
+            RepoMessage::Join { sender_id, .. } => {
+                self.peers.insert(sender_id.clone());
+                Ok(vec![RepoMessage::Peer {
+                    sender_id: self.peer_id.clone(),
+                    target_id: sender_id,
+                    selected_protocol_version: PROTOCOL_VERSION.to_owned(),
+                    peer_metadata: self.metadata.clone(),
+                }])
+            }
+            RepoMessage::Peer { sender_id, .. } => {
+                self.peers.insert(sender_id);
+                Ok(Vec::new())
+            }
+            RepoMessage::Request {
+                sender_id,
+                document_id,
+                ..
+            } if !self.docs.contains_key(&document_id) => Ok(vec![RepoMessage::DocUnavailable {
+                sender_id: self.peer_id.clone(),
+                target_id: sender_id,
+                document_id,
+            }]),
+            RepoMessage::Sync {
+                sender_id,
+                document_id,
+                data,
+                ..
+            }
+            | RepoMessage::Request {
+                sender_id,
+                document_id,
+                data,
+                ..
+            } => {
+                let message = Message::decode(&data)?;
+                let state = self.states.entry((sender_id, document_id)).or_default();
+                if let Some(doc) = self.docs.get_mut(&document_id) {
+                    doc.sync().receive_sync_message(state, message)?;
+                    return Ok(self.sync_messages(&document_id));
+                }
+                let mut doc = self.pending.remove(&document_id).unwrap_or_default();
+                doc.sync().receive_sync_message(state, message)?;
+                // Keep waiting until a message brings the changes of the document
+                if doc.get_heads().is_empty() {
+                    self.pending.insert(document_id, doc);
+                    return Ok(Vec::new());
+                }
+                self.docs.insert(document_id, doc);
+                Ok(self.sync_messages(&document_id))
+            }
+            RepoMessage::DocUnavailable {
+                sender_id,
+                document_id,
+                ..
+            } => {
+                self.states.remove(&(sender_id, document_id));
+                let waiting = self.states.keys().any(|(_, id)| *id == document_id);
+                if !waiting {
+                    self.pending.remove(&document_id);
+                }
+                Ok(Vec::new())
+            }
+            RepoMessage::Error { .. } => Ok(Vec::new()),
+        }
+    }
+}
@@ -4,6 +4,13 @@ pub enum Error {
     Custom(String),
     #[error("map keys must be a string")]
     KeysMustBeAString,
+    #[error("only maps, structs and sequences can be serialized into an existing object")]
+    ExpectedObject,
+    #[error("cannot serialize a {expected} into an existing {found}")]
+    ObjectTypeMismatch {
+        expected: automerge::ObjType,
+        found: automerge::ObjType,
+    },
     #[error(transparent)]
     AutomergeError(#[from] automerge::AutomergeError),
 }
@@ -1,11 +1,14 @@
 use super::{KeySerializer, Serializer};
 use automerge::{transaction::Transactable, ObjId};
 use serde::ser;
+use std::collections::HashSet;
 
 pub struct MapSerializer<'a, Tx: Transactable> {
     tx: &'a mut Tx,
     obj: ObjId,
     next_key: Option<String>,
+    reconcile: bool,
+    written: HashSet<String>,
 }
 
 impl<'a, Tx: Transactable> MapSerializer<'a, Tx> {
@@ -14,11 +17,18 @@ impl<'a, Tx: Transactable> MapSerializer<'a, Tx> {
             tx,
             obj,
             next_key: None,
+            reconcile: false,
+            written: HashSet::new(),
         }
     }
     pub fn new_root(tx: &'a mut Tx) -> Self {
         Self::new(tx, ObjId::Root)
     }
+    /// See [`Serializer::with_reconcile()`], keys that were not serialized are deleted on [`end()`][ser::SerializeMap::end].
+    pub fn with_reconcile(mut self, reconcile: bool) -> Self {
+        self.reconcile = reconcile;
+        self
+    }
 }
 
 impl<'a, Tx: Transactable> ser::SerializeMap for MapSerializer<'a, Tx> {
@@ -41,11 +51,26 @@ impl<'a, Tx: Transactable> ser::SerializeMap for MapSerializer<'a, Tx> {
             .next_key
             .take()
             .expect("serialize_value called before serialize_key");
-        value.serialize(Serializer::new(self.tx, self.obj.clone(), key))?;
+        value.serialize(
+            Serializer::new(self.tx, self.obj.clone(), key.as_str()).with_reconcile(self.reconcile),
+        )?;
+        if self.reconcile {
+            self.written.insert(key);
+        }
         Ok(())
     }
 
     fn end(self) -> Result<Self::Ok, Self::Error> {
+        if self.reconcile {
+            let stale = self
+                .tx
+                .keys(&self.obj)
+                .filter(|k| !self.written.contains(k))
+                .collect::<Vec<_>>();
+            for key in stale {
+                self.tx.delete(&self.obj, key)?;
+            }
+        }
         Ok((self.tx, self.obj))
     }
 }
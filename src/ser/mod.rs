@@ -1,4 +1,10 @@
-use automerge::{transaction::Transactable, AutomergeError, ObjId, ObjType, Prop, ScalarValue};
+// The serializer is written against the serde 1.0 signatures it was first built on
+#![allow(clippy::multiple_bound_locations, deprecated)]
+
+use crate::text::{capture, BlockText, RichText, BLOCK_TEXT, RICH_TEXT};
+use automerge::{
+    transaction::Transactable, AutomergeError, ObjId, ObjType, Prop, ScalarValue, Value,
+};
 use serde::{
     ser::{self},
     serde_if_integer128,
@@ -13,13 +19,15 @@ mod seq;
 pub use error::*;
 pub use key::*;
 pub use map::MapSerializer;
+#[allow(unused_imports)]
 pub use map::*;
 pub use seq::*;
 
 pub struct Serializer<'a, Tx: Transactable> {
     tx: &'a mut Tx,
     obj: ObjId,
-    prop: Prop,
+    prop: Option<Prop>,
+    reconcile: bool,
 }
 
 impl<'a, Tx: Transactable> Serializer<'a, Tx> {
@@ -27,30 +35,115 @@ impl<'a, Tx: Transactable> Serializer<'a, Tx> {
         Self {
             tx,
             obj,
-            prop: prop.into(),
+            prop: Some(prop.into()),
+            reconcile: false,
         }
     }
     pub fn new_root<P: Into<Prop>>(tx: &'a mut Tx, prop: P) -> Self {
         Self::new(tx, ObjId::Root, prop)
     }
-    fn put<V: Into<ScalarValue>>(self, value: V) -> Result<(&'a mut Tx, ObjId), AutomergeError> {
-        self.tx.put(&self.obj, self.prop, value)?;
+    /// Serialize directly into the existing object `obj` instead of into one of its properties.
+    ///
+    /// Only maps, structs and sequences can be written this way, and their type has to match
+    /// the type of `obj`.
+    pub fn new_object(tx: &'a mut Tx, obj: ObjId) -> Self {
+        Self {
+            tx,
+            obj,
+            prop: None,
+            reconcile: false,
+        }
+    }
+    /// Reconcile the value with what is already in the document rather than overwriting it.
+    ///
+    /// Unchanged scalars are not written, strings stored as text objects are updated in place,
+    /// existing objects of the right type are reused and map keys that are no longer part of the
+    /// value are deleted. Lists are diffed against their existing elements, so that inserting or
+    /// removing an element does not move the others onto different objects. This keeps concurrent
+    /// edits to untouched parts of the value intact when merging.
+    ///
+    /// A list element that changed is updated in place when it is a map that has an entry in
+    /// common with the existing element, and otherwise replaces the existing element at its
+    /// position, so a concurrent edit to an element that was replaced as a whole is lost.
+    pub fn with_reconcile(mut self, reconcile: bool) -> Self {
+        self.reconcile = reconcile;
+        self
+    }
+    fn put<V: Into<ScalarValue>>(self, value: V) -> Result<(&'a mut Tx, ObjId), Error> {
+        let prop = self.prop.ok_or(Error::ExpectedObject)?;
+        let value = value.into();
+        if self.reconcile {
+            if let Some((Value::Scalar(current), _)) = self.tx.get(&self.obj, prop.clone())? {
+                if *current == value {
+                    return Ok((self.tx, self.obj));
+                }
+            }
+        }
+        self.tx.put(&self.obj, prop, value)?;
         Ok((self.tx, self.obj))
     }
-    fn put_object(self, value: ObjType) -> Result<(&'a mut Tx, ObjId), AutomergeError> {
-        let obj = self.tx.put_object(&self.obj, self.prop, value)?;
+    /// Like [`put()`][Self::put()], but when reconciling a string that is stored as a text object,
+    /// which is how JavaScript clients store strings, the text is updated in place so that
+    /// concurrent edits to it merge.
+    fn put_str(self, value: &str) -> Result<(&'a mut Tx, ObjId), Error> {
+        if self.reconcile {
+            if let Some(prop) = &self.prop {
+                if let Some((Value::Object(ObjType::Text), id)) =
+                    self.tx.get(&self.obj, prop.clone())?
+                {
+                    if self.tx.text(&id)? != value {
+                        self.tx.update_text(&id, value)?;
+                    }
+                    return Ok((self.tx, self.obj));
+                }
+            }
+        }
+        self.put(value)
+    }
+    fn put_object(self, value: ObjType) -> Result<(&'a mut Tx, ObjId), Error> {
+        let Some(prop) = self.prop else {
+            let found = self.tx.object_type(&self.obj)?;
+            return match (value, found) {
+                (ObjType::Map, ObjType::Map | ObjType::Table) => Ok((self.tx, self.obj)),
+                (expected, found) if expected == found => Ok((self.tx, self.obj)),
+                (expected, found) => Err(Error::ObjectTypeMismatch { expected, found }),
+            };
+        };
+        if self.reconcile {
+            if let Some((Value::Object(current), obj)) = self.tx.get(&self.obj, prop.clone())? {
+                if current == value {
+                    return Ok((self.tx, obj));
+                }
+            }
+        }
+        let obj = self.tx.put_object(&self.obj, prop, value)?;
         Ok((self.tx, obj))
     }
-    fn put_variant(self, variant: &'static str) -> Result<Self, AutomergeError> {
+    fn put_variant(self, variant: &'static str) -> Result<Self, Error> {
+        let reconcile = self.reconcile;
         let (tx, obj) = self.put_object(ObjType::Map)?;
-        Ok(Self::new(tx, obj, variant))
+        if reconcile {
+            let stale = tx.keys(&obj).filter(|k| k != variant).collect::<Vec<_>>();
+            for key in stale {
+                tx.delete(&obj, key)?;
+            }
+        }
+        Ok(Self::new(tx, obj, variant).with_reconcile(reconcile))
+    }
+    fn put_text(
+        self,
+        write: impl FnOnce(&mut Tx, &ObjId) -> Result<(), AutomergeError>,
+    ) -> Result<(&'a mut Tx, ObjId), Error> {
+        let (tx, obj) = self.put_object(ObjType::Text)?;
+        write(tx, &obj)?;
+        Ok((tx, obj))
     }
 }
 
 macro_rules! serialize_put {
     ($method:ident, $type:ty$( as $as:ty)?) => {
         fn $method(self, v: $type) -> Result<Self::Ok, Self::Error> {
-            Ok(self.put(v$(as $as)?)?)
+            self.put(v$(as $as)?)
         }
     };
 }
@@ -88,10 +181,13 @@ impl<'a, Tx: Transactable> ser::Serializer for Serializer<'a, Tx> {
     serialize_put!(serialize_f64, f64);
 
     serialize_put!(serialize_char, char);
-    serialize_put!(serialize_str, &str);
+
+    fn serialize_str(self, v: &str) -> Result<Self::Ok, Self::Error> {
+        self.put_str(v)
+    }
 
     fn serialize_bytes(self, v: &[u8]) -> Result<Self::Ok, Self::Error> {
-        Ok(self.put(v.to_owned())?)
+        self.put(v.to_owned())
     }
 
     fn serialize_none(self) -> Result<Self::Ok, Self::Error> {
@@ -106,7 +202,7 @@ impl<'a, Tx: Transactable> ser::Serializer for Serializer<'a, Tx> {
     }
 
     fn serialize_unit(self) -> Result<Self::Ok, Self::Error> {
-        Ok(self.put(())?)
+        self.put(())
     }
 
     fn serialize_unit_struct(self, _name: &'static str) -> Result<Self::Ok, Self::Error> {
@@ -124,13 +220,23 @@ impl<'a, Tx: Transactable> ser::Serializer for Serializer<'a, Tx> {
 
     fn serialize_newtype_struct<T: ?Sized>(
         self,
-        _name: &'static str,
+        name: &'static str,
         value: &T,
     ) -> Result<Self::Ok, Self::Error>
     where
         T: serde::Serialize,
     {
-        value.serialize(self)
+        match name {
+            RICH_TEXT => {
+                let text: RichText = capture(value)?;
+                self.put_text(|tx, obj| text.write(tx, obj))
+            }
+            BLOCK_TEXT => {
+                let text: BlockText = capture(value)?;
+                self.put_text(|tx, obj| text.write(tx, obj))
+            }
+            _ => value.serialize(self),
+        }
     }
 
     fn serialize_newtype_variant<T: ?Sized>(
@@ -148,8 +254,9 @@ impl<'a, Tx: Transactable> ser::Serializer for Serializer<'a, Tx> {
     }
 
     fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq, Self::Error> {
+        let reconcile = self.reconcile;
         let (tx, obj) = self.put_object(ObjType::List)?;
-        Ok(SeqSerializer::new(tx, obj))
+        Ok(SeqSerializer::new(tx, obj).with_reconcile(reconcile))
     }
 
     fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple, Self::Error> {
@@ -175,8 +282,9 @@ impl<'a, Tx: Transactable> ser::Serializer for Serializer<'a, Tx> {
     }
 
     fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, Self::Error> {
+        let reconcile = self.reconcile;
         let (tx, obj) = self.put_object(ObjType::Map)?;
-        Ok(MapSerializer::new(tx, obj))
+        Ok(MapSerializer::new(tx, obj).with_reconcile(reconcile))
     }
 
     fn serialize_struct(
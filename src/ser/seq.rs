@@ -1,16 +1,163 @@
-use super::Serializer;
-use automerge::{transaction::Transactable, ObjId, ScalarValue};
-use serde::ser;
+use super::{Error, Serializer};
+use crate::value::Node;
+use automerge::{transaction::Transactable, AutoCommit, ObjId, ObjType, ScalarValue, ROOT};
+use serde::{ser, Serialize};
+
+/// When the elements between the common prefix and suffix of two lists take more comparisons than
+/// this to diff, [`align()`] leaves them to be matched by position instead.
+const MAX_DIFF_CELLS: usize = 1 << 20;
 
 pub struct SeqSerializer<'a, Tx: Transactable> {
     tx: &'a mut Tx,
     obj: ObjId,
     id: usize,
+    reconcile: bool,
+    /// When reconciling, the elements are written to a list in a scratch document first, so that
+    /// they can be diffed against the existing elements on [`end()`][ser::SerializeSeq::end].
+    scratch: Option<(AutoCommit, ObjId)>,
 }
 
 impl<'a, Tx: Transactable> SeqSerializer<'a, Tx> {
     pub fn new(tx: &'a mut Tx, obj: ObjId) -> Self {
-        Self { tx, obj, id: 0 }
+        Self {
+            tx,
+            obj,
+            id: 0,
+            reconcile: false,
+            scratch: None,
+        }
+    }
+    /// See [`Serializer::with_reconcile()`], the serialized elements are diffed against the
+    /// existing elements on [`end()`][ser::SerializeSeq::end]. Equal elements are kept, elements
+    /// that are no longer part of the value are deleted and new ones are inserted, so that
+    /// concurrent edits to the other elements stay on the same element.
+    pub fn with_reconcile(mut self, reconcile: bool) -> Self {
+        self.reconcile = reconcile;
+        self
+    }
+}
+
+/// Pairs of indices of the longest common subsequence of `old_len` and `new_len` elements, where
+/// `eq` tells whether two of them match.
+fn align(old_len: usize, new_len: usize, eq: impl Fn(usize, usize) -> bool) -> Vec<(usize, usize)> {
+    let prefix = (0..old_len.min(new_len)).take_while(|&i| eq(i, i)).count();
+    let suffix = (1..=old_len.min(new_len) - prefix)
+        .take_while(|&k| eq(old_len - k, new_len - k))
+        .count();
+    let (old_end, new_end) = (old_len - suffix, new_len - suffix);
+    let mut matches = (0..prefix).map(|i| (i, i)).collect::<Vec<_>>();
+
+    let (rows, columns) = (old_end - prefix, new_end - prefix);
+    if rows * columns <= MAX_DIFF_CELLS {
+        // Length of the longest common subsequence of the rows from i and the columns from j
+        let mut lengths = vec![0u32; (rows + 1) * (columns + 1)];
+        let at = |i: usize, j: usize| i * (columns + 1) + j;
+        for i in (0..rows).rev() {
+            for j in (0..columns).rev() {
+                lengths[at(i, j)] = if eq(prefix + i, prefix + j) {
+                    lengths[at(i + 1, j + 1)] + 1
+                } else {
+                    lengths[at(i + 1, j)].max(lengths[at(i, j + 1)])
+                };
+            }
+        }
+        let (mut i, mut j) = (0, 0);
+        while i < rows && j < columns {
+            if eq(prefix + i, prefix + j) {
+                matches.push((prefix + i, prefix + j));
+                i += 1;
+                j += 1;
+            } else if lengths[at(i + 1, j)] >= lengths[at(i, j + 1)] {
+                i += 1;
+            } else {
+                j += 1;
+            }
+        }
+    }
+
+    matches.extend((0..suffix).map(|k| (old_end + k, new_end + k)));
+    matches
+}
+
+/// What to do with the next element of the list.
+enum Edit {
+    Keep,
+    /// Reconcile the element with this new element.
+    Update(usize),
+    Delete,
+    Insert(usize),
+}
+
+/// The edits that turn the `old` elements into the `new` ones.
+///
+/// Elements that are [`Node::same()`] are kept. Of the elements in between, maps that still have
+/// an entry in common are updated in place, so that concurrent edits to them are not lost, and the
+/// remaining ones take the place of the old elements by position.
+fn edits(old: &[Node], new: &[Node]) -> Vec<Edit> {
+    let mut edits = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    let end = (old.len(), new.len());
+    for (next_old, next_new) in align(old.len(), new.len(), |i, j| old[i].same(&new[j]))
+        .into_iter()
+        .chain([end])
+    {
+        let (old_gap, new_gap) = (&old[i..next_old], &new[j..next_new]);
+        let similar = align(old_gap.len(), new_gap.len(), |a, b| {
+            old_gap[a].similar(&new_gap[b])
+        });
+        let (mut a, mut b) = (0, 0);
+        for (next_a, next_b) in similar.into_iter().chain([(old_gap.len(), new_gap.len())]) {
+            let replaced = (next_a - a).min(next_b - b);
+            edits.extend((j + b..j + b + replaced).map(Edit::Update));
+            edits.extend((a + replaced..next_a).map(|_| Edit::Delete));
+            edits.extend((j + b + replaced..j + next_b).map(Edit::Insert));
+            if next_b < new_gap.len() {
+                edits.push(Edit::Update(j + next_b));
+            }
+            (a, b) = (next_a + 1, next_b + 1);
+        }
+        if next_new < new.len() {
+            edits.push(Edit::Keep);
+        }
+        (i, j) = (next_old + 1, next_new + 1);
+    }
+    edits
+}
+
+impl<'a, Tx: Transactable> SeqSerializer<'a, Tx> {
+    /// Turn the existing elements into the serialized ones with as few changes as possible.
+    fn apply_diff(self) -> Result<(&'a mut Tx, ObjId), Error> {
+        let new = match &self.scratch {
+            Some((scratch, list)) => match Node::read(scratch, ObjType::List.into(), list, None)? {
+                Node::List(items) => items,
+                _ => unreachable!("the scratch list is a list"),
+            },
+            None => Vec::new(),
+        };
+        let old = match Node::read(&*self.tx, ObjType::List.into(), &self.obj, None)? {
+            Node::List(items) => items,
+            _ => unreachable!("only lists are serialized as sequences"),
+        };
+
+        let mut index = 0;
+        for edit in edits(&old, &new) {
+            match edit {
+                Edit::Keep => index += 1,
+                Edit::Update(j) => {
+                    new[j].serialize(
+                        Serializer::new(&mut *self.tx, self.obj.clone(), index)
+                            .with_reconcile(true),
+                    )?;
+                    index += 1;
+                }
+                Edit::Delete => self.tx.delete(&self.obj, index)?,
+                Edit::Insert(j) => {
+                    new[j].write(self.tx, &self.obj, index.into(), true)?;
+                    index += 1;
+                }
+            }
+        }
+        Ok((self.tx, self.obj))
     }
 }
 
@@ -22,15 +169,31 @@ impl<'a, Tx: Transactable> ser::SerializeSeq for SeqSerializer<'a, Tx> {
     where
         T: serde::Serialize,
     {
-        if self.id == self.tx.length(&self.obj) {
-            self.tx.insert(&self.obj, self.id, ScalarValue::Null)?;
+        if self.reconcile {
+            let (scratch, list) = match &mut self.scratch {
+                Some(scratch) => scratch,
+                scratch => {
+                    let mut doc = AutoCommit::new();
+                    let list = doc.put_object(ROOT, "list", ObjType::List)?;
+                    scratch.insert((doc, list))
+                }
+            };
+            scratch.insert(&*list, self.id, ScalarValue::Null)?;
+            value.serialize(Serializer::new(scratch, list.clone(), self.id))?;
+        } else {
+            if self.id == self.tx.length(&self.obj) {
+                self.tx.insert(&self.obj, self.id, ScalarValue::Null)?;
+            }
+            value.serialize(Serializer::new(self.tx, self.obj.clone(), self.id))?;
         }
-        value.serialize(Serializer::new(self.tx, self.obj.clone(), self.id))?;
         self.id += 1;
         Ok(())
     }
 
     fn end(self) -> Result<Self::Ok, Self::Error> {
+        if self.reconcile {
+            return self.apply_diff();
+        }
         Ok((self.tx, self.obj))
     }
 }
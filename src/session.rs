@@ -0,0 +1,81 @@
+use crate::{
+    patch::{overlaps, targets},
+    AutomergeSerdeError, TypedDoc,
+};
+use automerge::{
+    patches::TextRepresentation,
+    sync::{Message, State, SyncDoc},
+    Prop,
+};
+use serde::{de::DeserializeOwned, Serialize};
+
+/// Whether `path` is one of the `changed` paths returned by [`SyncSession::receive_message()`],
+/// or is inside of or contains one of them.
+pub fn is_changed(changed: &[Vec<Prop>], path: &[Prop]) -> bool {
+    changed.iter().any(|c| overlaps(c, path))
+}
+
+/// Runs automerge's sync protocol for a [`TypedDoc`] with a single peer.
+///
+/// Messages are exchanged until [`SyncSession::generate_message()`] returns [`None`] on both
+/// sides, after which both documents contain the same changes. How messages get to the other
+/// peer is up to the caller, [`Message::encode()`] and [`Message::decode()`] turn them into bytes.
+pub struct SyncSession<T> {
+    doc: TypedDoc<T>,
+    state: State,
+}
+
+impl<T: Serialize + DeserializeOwned> SyncSession<T> {
+    pub fn new(doc: TypedDoc<T>) -> Self {
+        Self::with_state(doc, State::new())
+    }
+    /// Resume a session with a peer using the `state` from an earlier session, see
+    /// [`State::encode()`].
+    pub fn with_state(doc: TypedDoc<T>, state: State) -> Self {
+        Self { doc, state }
+    }
+
+    /// The next message to send to the peer, or [`None`] if the peer is known to be in sync.
+    pub fn generate_message(&mut self) -> Option<Message> {
+        self.doc
+            .doc_mut()
+            .sync()
+            .generate_sync_message(&mut self.state)
+    }
+    /// Apply a message from the peer and return the paths below the root that changed, without
+    /// paths that are inside of another returned path.
+    pub fn receive_message(
+        &mut self,
+        message: Message,
+    ) -> Result<Vec<Vec<Prop>>, AutomergeSerdeError> {
+        let doc = self.doc.doc_mut();
+        let before = doc.get_heads();
+        doc.sync().receive_sync_message(&mut self.state, message)?;
+        let after = doc.get_heads();
+        if before == after {
+            return Ok(Vec::new());
+        }
+        let doc = doc.document();
+        let patches = doc.diff(
+            &before,
+            &after,
+            TextRepresentation::String(doc.text_encoding()),
+        );
+        Ok(targets(patches))
+    }
+    pub fn read(&self) -> Result<T, AutomergeSerdeError> {
+        self.doc.read()
+    }
+    pub fn state(&self) -> &State {
+        &self.state
+    }
+    pub fn doc(&self) -> &TypedDoc<T> {
+        &self.doc
+    }
+    pub fn doc_mut(&mut self) -> &mut TypedDoc<T> {
+        &mut self.doc
+    }
+    pub fn into_parts(self) -> (TypedDoc<T>, State) {
+        (self.doc, self.state)
+    }
+}
@@ -0,0 +1,141 @@
+use crate::{patch::targets, AutomergeSerdeError, Deserializer, Serializer};
+use automerge::{AutoCommit, ChangeHash, ObjId, Prop};
+use serde::{de::DeserializeOwned, Serialize};
+use std::{
+    marker::PhantomData,
+    sync::{
+        mpsc::{channel, Receiver, Sender},
+        Arc, Mutex, PoisonError, RwLock,
+    },
+};
+
+/// Receives the paths below the root changed by every change.
+type Subscriber = Sender<Vec<Vec<Prop>>>;
+
+/// Reconcile `value` into the root of `doc`, rolling back on errors.
+fn reconcile<T: Serialize>(doc: &mut AutoCommit, value: &T) -> Result<(), AutomergeSerdeError> {
+    let result = value
+        .serialize(Serializer::new_object(doc, ObjId::Root).with_reconcile(true))
+        .map(|_| ());
+    if result.is_err() {
+        doc.rollback();
+    }
+    Ok(result?)
+}
+
+/// A document whose root map holds a value of type `T`, shared between threads.
+///
+/// Clones refer to the same document. Every change made through [`SharedDoc::modify()`] or merged
+/// in through [`SharedDoc::merge_from_bytes()`] sends the paths below the root that it changed to
+/// all receivers returned by [`SharedDoc::subscribe()`].
+pub struct SharedDoc<T> {
+    doc: Arc<RwLock<AutoCommit>>,
+    /// The heads after the last change, so that reading them does not need exclusive access.
+    heads: Arc<RwLock<Vec<ChangeHash>>>,
+    subscribers: Arc<Mutex<Vec<Subscriber>>>,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T> Clone for SharedDoc<T> {
+    fn clone(&self) -> Self {
+        Self {
+            doc: self.doc.clone(),
+            heads: self.heads.clone(),
+            subscribers: self.subscribers.clone(),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T: Serialize + DeserializeOwned> SharedDoc<T> {
+    pub fn new(value: &T) -> Result<Self, AutomergeSerdeError> {
+        let doc = Self::from_doc(AutoCommit::new());
+        doc.write(value)?;
+        Ok(doc)
+    }
+    pub fn from_doc(mut doc: AutoCommit) -> Self {
+        Self {
+            heads: Arc::new(RwLock::new(doc.get_heads())),
+            doc: Arc::new(RwLock::new(doc)),
+            subscribers: Default::default(),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Receive the paths changed by every following change to the document.
+    pub fn subscribe(&self) -> Receiver<Vec<Vec<Prop>>> {
+        let (tx, rx) = channel();
+        self.subscribers
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(tx);
+        rx
+    }
+
+    /// Run `f` with shared access to the document, while other threads can read it as well.
+    pub fn with_doc<R>(&self, f: impl FnOnce(&AutoCommit) -> R) -> R {
+        f(&self.doc.read().unwrap_or_else(PoisonError::into_inner))
+    }
+    /// Run `f` with exclusive access to the document and notify subscribers of the changes it
+    /// committed.
+    pub fn with_doc_mut<R>(&self, f: impl FnOnce(&mut AutoCommit) -> R) -> R {
+        let mut doc = self.lock_mut();
+        // Pending ops of a thread that panicked while holding the lock are committed along with
+        // this change, and reach the subscribers as they come after the last recorded heads
+        let before = self.get_heads();
+        let result = f(&mut doc);
+        doc.commit();
+        let after = doc.get_heads();
+        if before != after {
+            let changed = targets(doc.diff(&before, &after));
+            *self.heads.write().unwrap_or_else(PoisonError::into_inner) = after;
+            self.notify(changed);
+        }
+        result
+    }
+
+    fn notify(&self, changed: Vec<Vec<Prop>>) {
+        let mut subscribers = self
+            .subscribers
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        subscribers.retain(|tx| tx.send(changed.clone()).is_ok());
+    }
+
+    pub fn read(&self) -> Result<T, AutomergeSerdeError> {
+        self.with_doc(|doc| Ok(T::deserialize(Deserializer::new_root(doc))?))
+    }
+    /// Reconcile `value` into the document and commit the result as a single change.
+    pub fn write(&self, value: &T) -> Result<(), AutomergeSerdeError> {
+        self.with_doc_mut(|doc| reconcile(doc, value))
+    }
+    /// Read the current value, let `f` modify it and reconcile the result back into the document,
+    /// all while holding the lock so that no other change can come in between.
+    pub fn modify<R>(&self, f: impl FnOnce(&mut T) -> R) -> Result<R, AutomergeSerdeError> {
+        self.with_doc_mut(|doc| {
+            let mut value = T::deserialize(Deserializer::new_root(&*doc))?;
+            let result = f(&mut value);
+            reconcile(doc, &value)?;
+            Ok(result)
+        })
+    }
+    /// Merge changes from a document saved with `save()` or `save_incremental()`.
+    pub fn merge_from_bytes(&self, data: &[u8]) -> Result<usize, AutomergeSerdeError> {
+        Ok(self.with_doc_mut(|doc| doc.load_incremental(data))?)
+    }
+
+    fn lock_mut(&self) -> std::sync::RwLockWriteGuard<'_, AutoCommit> {
+        self.doc.write().unwrap_or_else(PoisonError::into_inner)
+    }
+    /// Saving needs exclusive access to the document, as [`AutoCommit::save()`] does.
+    pub fn save(&self) -> Vec<u8> {
+        self.lock_mut().save()
+    }
+    /// The heads after the last change, without waiting for readers of the document.
+    pub fn get_heads(&self) -> Vec<ChangeHash> {
+        self.heads
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+}
@@ -0,0 +1,89 @@
+use super::Storage;
+use std::{
+    fs,
+    io::{Error, ErrorKind, Write},
+    path::{Component, Path, PathBuf},
+};
+
+/// Stores every key as a file below a root directory.
+///
+/// Values are written to a temporary file that is synced and then renamed over the old file.
+/// Keys whose parts are empty, `.`, `..` or anything else than a plain file name are rejected
+/// with [`ErrorKind::InvalidInput`], so that no key reaches outside of the root.
+#[derive(Debug, Clone)]
+pub struct FsStorage {
+    root: PathBuf,
+}
+
+impl FsStorage {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+    fn path(&self, key: &str) -> std::io::Result<PathBuf> {
+        let mut path = self.root.clone();
+        for part in key.split('/') {
+            match Path::new(part).components().collect::<Vec<_>>().as_slice() {
+                [Component::Normal(name)] if *name == part => path.push(part),
+                _ => {
+                    let message = format!("invalid storage key `{key}`");
+                    return Err(Error::new(ErrorKind::InvalidInput, message));
+                }
+            }
+        }
+        Ok(path)
+    }
+}
+
+impl Storage for FsStorage {
+    fn get(&self, key: &str) -> std::io::Result<Option<Vec<u8>>> {
+        match fs::read(self.path(key)?) {
+            Ok(data) => Ok(Some(data)),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+    fn put(&mut self, key: &str, data: &[u8]) -> std::io::Result<()> {
+        let path = self.path(key)?;
+        let dir = path.parent().expect("keys are below the root");
+        fs::create_dir_all(dir)?;
+        let mut tmp = path.clone().into_os_string();
+        tmp.push(".tmp");
+        let mut file = fs::File::create(&tmp)?;
+        file.write_all(data)?;
+        file.sync_all()?;
+        fs::rename(tmp, &path)?;
+        // The rename is only durable once the directory that holds the file is synced
+        #[cfg(unix)]
+        fs::File::open(dir)?.sync_all()?;
+        Ok(())
+    }
+    fn remove(&mut self, key: &str) -> std::io::Result<()> {
+        match fs::remove_file(self.path(key)?) {
+            Err(e) if e.kind() != ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        }
+    }
+    fn list(&self, dir: &str) -> std::io::Result<Vec<String>> {
+        let entries = match fs::read_dir(self.path(dir)?) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e),
+        };
+        let mut names = Vec::new();
+        for entry in entries {
+            let entry = entry?;
+            let name = entry.file_name();
+            // Skip directories and leftovers of interrupted writes
+            match name.to_str() {
+                Some(name) if entry.file_type()?.is_file() && !name.ends_with(".tmp") => {
+                    names.push(name.to_owned())
+                }
+                _ => {}
+            }
+        }
+        Ok(names)
+    }
+}
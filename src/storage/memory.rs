@@ -0,0 +1,42 @@
+use super::Storage;
+use std::collections::BTreeMap;
+
+/// Keeps all data in memory, for tests and documents that don't outlive the process.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryStorage {
+    entries: BTreeMap<String, Vec<u8>>,
+}
+
+impl MemoryStorage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn keys(&self) -> impl Iterator<Item = &str> {
+        self.entries.keys().map(String::as_str)
+    }
+}
+
+impl Storage for MemoryStorage {
+    fn get(&self, key: &str) -> std::io::Result<Option<Vec<u8>>> {
+        Ok(self.entries.get(key).cloned())
+    }
+    fn put(&mut self, key: &str, data: &[u8]) -> std::io::Result<()> {
+        self.entries.insert(key.to_owned(), data.to_vec());
+        Ok(())
+    }
+    fn remove(&mut self, key: &str) -> std::io::Result<()> {
+        self.entries.remove(key);
+        Ok(())
+    }
+    fn list(&self, dir: &str) -> std::io::Result<Vec<String>> {
+        let prefix = format!("{dir}/");
+        Ok(self
+            .entries
+            .range(prefix.clone()..)
+            .map(|(key, _)| key)
+            .take_while(|key| key.starts_with(&prefix))
+            .filter_map(|key| Some(key.strip_prefix(&prefix)?.to_owned()))
+            .filter(|name| !name.contains('/'))
+            .collect())
+    }
+}
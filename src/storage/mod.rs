@@ -0,0 +1,172 @@
+//! Persist documents as a snapshot plus incremental chunks in a pluggable [`Storage`].
+
+use crate::{AutomergeSerdeError, Deserializer, TypedDoc};
+use automerge::{Automerge, ChangeHash};
+use serde::{de::DeserializeOwned, Serialize};
+use sha2::{Digest, Sha256};
+
+mod fs;
+mod memory;
+
+pub use fs::FsStorage;
+pub use memory::MemoryStorage;
+
+/// A key-value store for the bytes of documents.
+///
+/// Keys are `/`-separated paths. [`Storage::put()`] has to replace the value atomically, so that a
+/// crash leaves either the old or the new value behind.
+pub trait Storage {
+    fn get(&self, key: &str) -> std::io::Result<Option<Vec<u8>>>;
+    fn put(&mut self, key: &str, data: &[u8]) -> std::io::Result<()>;
+    /// Removing a key that does not exist is not an error.
+    fn remove(&mut self, key: &str) -> std::io::Result<()>;
+    /// The names of the keys directly inside of `dir`, in any order.
+    fn list(&self, dir: &str) -> std::io::Result<Vec<String>>;
+}
+
+/// Deserialize the value at the root of a document saved with [`Automerge::save()`].
+pub fn from_bytes<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, AutomergeSerdeError> {
+    let doc = Automerge::load(bytes)?;
+    Ok(T::deserialize(Deserializer::new_root(&doc))?)
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+/// The directory that a document named `name` is stored in: the first two characters of its first
+/// `/`-separated part, and then the rest of the name.
+pub(crate) fn document_dir(name: &str) -> String {
+    let first = name.split('/').next().unwrap_or_default();
+    match first.char_indices().nth(2) {
+        Some((split, _)) => format!("{}/{}", &name[..split], &name[split..]),
+        None => name.to_owned(),
+    }
+}
+
+/// The name that automerge-repo stores a snapshot of a document with `heads` under.
+pub(crate) fn snapshot_hash(heads: &[ChangeHash]) -> String {
+    let mut sha = Sha256::new();
+    for head in heads {
+        sha.update(head.to_string());
+    }
+    hex(&sha.finalize())
+}
+
+/// The name that automerge-repo stores an incremental chunk under.
+pub(crate) fn chunk_hash(chunk: &[u8]) -> String {
+    hex(&Sha256::digest(chunk))
+}
+
+/// Stores every document under its name in the key layout of automerge-repo: snapshots in
+/// `{dir}/snapshot/` named by the hash of their heads, and chunks in `{dir}/incremental/` named
+/// by the hash of their bytes. Like in the NodeFS storage adapter, `{dir}` splits the first two
+/// characters of the name off into their own directory, so that documents named by their
+/// [`DocumentId`][crate::repo::DocumentId] are stored where automerge-repo in JavaScript looks for
+/// them, see [`StorageKey::to_path()`][crate::repo::StorageKey::to_path()].
+///
+/// [`DocStore::save()`] only writes the changes since the last save. Once there are more than
+/// [`DocStore::with_compact_after()`] chunks they are compacted into a new snapshot, which is
+/// written before the old snapshots and chunks are removed so that a crash in between loses
+/// nothing.
+pub struct DocStore<S> {
+    storage: S,
+    compact_after: usize,
+}
+
+impl<S: Storage> DocStore<S> {
+    pub fn new(storage: S) -> Self {
+        Self {
+            storage,
+            compact_after: 64,
+        }
+    }
+    pub fn with_compact_after(mut self, chunks: usize) -> Self {
+        self.compact_after = chunks;
+        self
+    }
+    pub fn storage(&self) -> &S {
+        &self.storage
+    }
+    pub fn into_storage(self) -> S {
+        self.storage
+    }
+
+    /// The keys of `name` of the given kind, `snapshot` or `incremental`.
+    fn keys(&self, name: &str, kind: &str) -> std::io::Result<Vec<String>> {
+        let dir = format!("{}/{kind}", document_dir(name));
+        Ok(self
+            .storage
+            .list(&dir)?
+            .into_iter()
+            .map(|key| format!("{dir}/{key}"))
+            .collect())
+    }
+
+    /// Load the document stored under `name`, or [`None`] if nothing was stored.
+    pub fn load<T: Serialize + DeserializeOwned>(
+        &self,
+        name: &str,
+    ) -> Result<Option<TypedDoc<T>>, AutomergeSerdeError> {
+        let mut keys = self.keys(name, "snapshot")?;
+        keys.extend(self.keys(name, "incremental")?);
+        if keys.is_empty() {
+            return Ok(None);
+        }
+        // Snapshots and chunks can be loaded in any order, automerge waits for missing changes
+        let mut doc = TypedDoc::from_doc(Default::default());
+        for key in keys {
+            if let Some(data) = self.storage.get(&key)? {
+                doc.load_incremental(&data)?;
+            }
+        }
+        // The loaded chunks are already stored, don't save them again
+        doc.save_incremental();
+        Ok(Some(doc))
+    }
+    /// Load only the value stored under `name`.
+    pub fn load_value<T: Serialize + DeserializeOwned>(
+        &self,
+        name: &str,
+    ) -> Result<Option<T>, AutomergeSerdeError> {
+        self.load::<T>(name)?.map(|doc| doc.read()).transpose()
+    }
+
+    /// Store the changes to `doc` since it was last saved or loaded as a new chunk, and compact
+    /// the chunks when there are too many.
+    pub fn save<T: Serialize + DeserializeOwned>(
+        &mut self,
+        name: &str,
+        doc: &mut TypedDoc<T>,
+    ) -> Result<(), AutomergeSerdeError> {
+        let chunk = doc.save_incremental();
+        if chunk.is_empty() {
+            return Ok(());
+        }
+        if self.keys(name, "incremental")?.len() >= self.compact_after {
+            return self.compact(name, doc);
+        }
+        let key = format!("{}/incremental/{}", document_dir(name), chunk_hash(&chunk));
+        self.storage.put(&key, &chunk)?;
+        Ok(())
+    }
+    /// Replace the snapshots and all chunks of `name` with a snapshot of `doc`.
+    pub fn compact<T: Serialize + DeserializeOwned>(
+        &mut self,
+        name: &str,
+        doc: &mut TypedDoc<T>,
+    ) -> Result<(), AutomergeSerdeError> {
+        let mut old = self.keys(name, "snapshot")?;
+        old.extend(self.keys(name, "incremental")?);
+        let key = format!(
+            "{}/snapshot/{}",
+            document_dir(name),
+            snapshot_hash(&doc.get_heads())
+        );
+        self.storage.put(&key, &doc.save())?;
+        for old in old.into_iter().filter(|old| *old != key) {
+            self.storage.remove(&old)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,248 @@
+use super::{rich::normalize, write_marks, Span};
+use automerge::{
+    hydrate, iter::Span as AutomergeSpan, transaction::Transactable, AutomergeError, BlockOrText,
+    ChangeHash, ObjId, ReadDoc, ScalarValue,
+};
+use serde::{de, Deserialize, Serialize};
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, HashMap},
+};
+
+/// Newtype struct name that the [`crate::Serializer`] and [`crate::Deserializer`] recognize to
+/// store a [`BlockText`] as an [`automerge::ObjType::Text`] object with block markers and marks.
+pub(crate) const BLOCK_TEXT: &str = "$serde_automerge::BlockText";
+
+/// A block, such as a paragraph, heading or list item, and the inline spans it contains.
+///
+/// The `block_type`, `parents` and `attrs` are stored in the block marker map using the same
+/// layout as `automerge-prosemirror`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Block {
+    #[serde(rename = "type")]
+    pub block_type: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub parents: Vec<String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub attrs: BTreeMap<String, ScalarValue>,
+    #[serde(default)]
+    pub spans: Vec<Span>,
+}
+
+impl Block {
+    pub fn new(block_type: impl Into<String>) -> Self {
+        Self {
+            block_type: block_type.into(),
+            ..Default::default()
+        }
+    }
+    pub fn with_parent(mut self, parent: impl Into<String>) -> Self {
+        self.parents.push(parent.into());
+        self
+    }
+    pub fn with_attr(mut self, name: impl Into<String>, value: impl Into<ScalarValue>) -> Self {
+        self.attrs.insert(name.into(), value.into());
+        self
+    }
+    pub fn with_span(mut self, span: Span) -> Self {
+        self.spans.push(span);
+        self
+    }
+    /// The text of this block without any marks.
+    pub fn text(&self) -> String {
+        self.spans.iter().map(|s| s.text.as_str()).collect()
+    }
+
+    fn from_marker(marker: &hydrate::Map) -> Self {
+        let scalar = |value: &hydrate::Value| match value {
+            hydrate::Value::Scalar(s) => Some(s.clone()),
+            _ => None,
+        };
+        let string = |value: &hydrate::Value| match value {
+            hydrate::Value::Scalar(ScalarValue::Str(s)) => Some(s.to_string()),
+            _ => None,
+        };
+        Self {
+            block_type: marker.get("type").and_then(string).unwrap_or_default(),
+            parents: match marker.get("parents") {
+                Some(hydrate::Value::List(parents)) => {
+                    parents.iter().filter_map(|p| string(&p.value)).collect()
+                }
+                _ => Vec::new(),
+            },
+            attrs: match marker.get("attrs") {
+                Some(hydrate::Value::Map(attrs)) => attrs
+                    .iter()
+                    .filter_map(|(k, v)| Some((k.clone(), scalar(&v.value)?)))
+                    .collect(),
+                _ => BTreeMap::new(),
+            },
+            spans: Vec::new(),
+        }
+    }
+
+    fn to_marker(&self) -> hydrate::Map {
+        let parents = self
+            .parents
+            .iter()
+            .map(|p| hydrate::Value::Scalar(p.as_str().into()))
+            .collect::<Vec<_>>();
+        let attrs = self
+            .attrs
+            .iter()
+            .map(|(k, v)| (k.clone(), hydrate::Value::Scalar(v.clone())))
+            .collect::<HashMap<_, _>>();
+        hydrate::Map::from(HashMap::from([
+            (
+                "type".to_owned(),
+                hydrate::Value::Scalar(self.block_type.as_str().into()),
+            ),
+            ("parents".to_owned(), parents.into()),
+            ("attrs".to_owned(), hydrate::Map::from(attrs).into()),
+        ]))
+    }
+}
+
+/// Text that is split into [`Block`]s, such as paragraphs, headings and list items.
+///
+/// Through this crate a [`BlockText`] is stored as an [`automerge::ObjType::Text`] object with
+/// block markers, which is updated with [`Transactable::update_spans()`] so that concurrent edits
+/// to the same text merge. Other serde formats see a map with the `leading` spans and `blocks`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BlockText {
+    /// Inline spans before the first block marker.
+    pub leading: Vec<Span>,
+    pub blocks: Vec<Block>,
+}
+
+impl BlockText {
+    pub fn new(blocks: Vec<Block>) -> Self {
+        Self {
+            leading: Vec::new(),
+            blocks,
+        }
+    }
+    /// Drop empty spans and join adjacent spans that have the same marks.
+    pub fn normalize(&mut self) {
+        normalize(&mut self.leading);
+        for block in &mut self.blocks {
+            normalize(&mut block.spans);
+        }
+    }
+
+    pub(crate) fn read<Rx: ReadDoc>(
+        doc: &Rx,
+        obj: &ObjId,
+        heads: Option<&[ChangeHash]>,
+    ) -> Result<Self, AutomergeError> {
+        let spans = match heads {
+            Some(heads) => doc.spans_at(obj, heads)?,
+            None => doc.spans(obj)?,
+        };
+        let mut text = Self::default();
+        for span in spans {
+            match span {
+                AutomergeSpan::Block(marker) => text.blocks.push(Block::from_marker(&marker)),
+                span => {
+                    let spans = match text.blocks.last_mut() {
+                        Some(block) => &mut block.spans,
+                        None => &mut text.leading,
+                    };
+                    spans.extend(Span::from_automerge(span));
+                }
+            }
+        }
+        text.normalize();
+        Ok(text)
+    }
+
+    /// Update the text object `obj` to contain exactly these blocks, texts and marks.
+    pub(crate) fn write<Tx: Transactable>(
+        &self,
+        tx: &mut Tx,
+        obj: &ObjId,
+    ) -> Result<(), AutomergeError> {
+        let leading = self
+            .leading
+            .iter()
+            .map(|s| s.text.as_str())
+            .collect::<String>();
+        let mut items = Vec::new();
+        if !leading.is_empty() {
+            items.push(BlockOrText::Text(leading.into()));
+        }
+        for block in &self.blocks {
+            items.push(BlockOrText::Block(block.to_marker()));
+            let text = block.text();
+            if !text.is_empty() {
+                items.push(BlockOrText::Text(text.into()));
+            }
+        }
+        tx.update_spans(obj, items)?;
+
+        let encoding = tx.text_encoding();
+        let mut start = 0;
+        let mut spans = Vec::new();
+        let blocks = self.blocks.iter().map(|block| (1, &block.spans));
+        for (marker, block_spans) in std::iter::once((0, &self.leading)).chain(blocks) {
+            // Block markers take up one position
+            start += marker;
+            for span in block_spans {
+                spans.push((start, span));
+                start += crate::handle::width(&span.text, encoding);
+            }
+        }
+        write_marks(tx, obj, spans)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct Fields<'a> {
+    #[serde(default, skip_serializing_if = "<[Span]>::is_empty")]
+    leading: Cow<'a, [Span]>,
+    #[serde(default)]
+    blocks: Cow<'a, [Block]>,
+}
+
+impl BlockText {
+    /// The contents of the [`BLOCK_TEXT`] newtype struct.
+    pub(crate) fn fields(&self) -> impl Serialize + '_ {
+        Fields {
+            leading: Cow::Borrowed(&self.leading),
+            blocks: Cow::Borrowed(&self.blocks),
+        }
+    }
+}
+
+impl Serialize for BlockText {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_newtype_struct(BLOCK_TEXT, &self.fields())
+    }
+}
+
+impl<'de> Deserialize<'de> for BlockText {
+    fn deserialize<D: de::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct Visitor;
+
+        impl<'de> de::Visitor<'de> for Visitor {
+            type Value = BlockText;
+
+            fn expecting(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                formatter.write_str("a map of leading spans and blocks")
+            }
+
+            fn visit_newtype_struct<D: de::Deserializer<'de>>(
+                self,
+                deserializer: D,
+            ) -> Result<Self::Value, D::Error> {
+                let Fields { leading, blocks } = Fields::deserialize(deserializer)?;
+                Ok(BlockText {
+                    leading: leading.into_owned(),
+                    blocks: blocks.into_owned(),
+                })
+            }
+        }
+
+        deserializer.deserialize_newtype_struct(BLOCK_TEXT, Visitor)
+    }
+}
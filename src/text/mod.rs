@@ -0,0 +1,141 @@
+use crate::{de, diff::FieldValue, ser};
+use automerge::{
+    marks::{ExpandMark, Mark},
+    transaction::Transactable,
+    AutomergeError, ObjId, ScalarValue,
+};
+use serde::{de::DeserializeOwned, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+
+mod block;
+mod rich;
+
+pub(crate) use block::BLOCK_TEXT;
+pub use block::{Block, BlockText};
+pub(crate) use rich::RICH_TEXT;
+pub use rich::{RichText, Span};
+
+/// Recover a [`RichText`] or [`BlockText`] from its `Serialize` implementation.
+///
+/// They only reach the [`Serializer`][crate::Serializer] as an opaque `Serialize` value, this
+/// reads it back in memory so that it can be diffed against the text object in the document.
+pub(crate) fn capture<T: ?Sized + Serialize, R: DeserializeOwned>(
+    value: &T,
+) -> Result<R, ser::Error> {
+    R::deserialize(FieldValue::from_serialize(value)?)
+        .map_err(<ser::Error as serde::ser::Error>::custom)
+}
+
+/// The inverse of [`capture()`]: hand `value` to `visitor` as if it was stored as plain maps and
+/// lists, which is what the `Deserialize` implementations of [`RichText`] and [`BlockText`]
+/// understand.
+pub(crate) fn replay<'de, T: Serialize, V: serde::de::Visitor<'de>>(
+    value: &T,
+    visitor: V,
+) -> Result<V::Value, de::Error> {
+    let value =
+        FieldValue::from_serialize(value).map_err(<de::Error as serde::de::Error>::custom)?;
+    visitor.visit_newtype_struct(value)
+}
+
+/// Mark and unmark ranges of `obj` until its marks match the `spans`, which start at the given
+/// positions in the text encoding of the document.
+pub(crate) fn write_marks<'s, Tx: Transactable>(
+    tx: &mut Tx,
+    obj: &ObjId,
+    spans: impl IntoIterator<Item = (usize, &'s Span)>,
+) -> Result<(), AutomergeError> {
+    let encoding = tx.text_encoding();
+    let mut desired = BTreeMap::<String, Vec<Run>>::new();
+    for (start, span) in spans {
+        let end = start + crate::handle::width(&span.text, encoding);
+        for (name, value) in span.marks.iter().filter(|(_, v)| !v.is_null()) {
+            if start < end {
+                desired
+                    .entry(name.clone())
+                    .or_default()
+                    .push((start, end, value.clone()));
+            }
+        }
+    }
+    let mut current = BTreeMap::<String, Vec<Run>>::new();
+    for mark in tx.marks(obj)? {
+        current.entry(mark.name().to_owned()).or_default().push((
+            mark.start,
+            mark.end,
+            mark.value().clone(),
+        ));
+    }
+
+    let names = desired
+        .keys()
+        .chain(current.keys())
+        .cloned()
+        .collect::<BTreeSet<_>>();
+    for name in names {
+        let want = desired.remove(&name).unwrap_or_default();
+        let mut have = current.remove(&name).unwrap_or_default();
+        have.sort_by_key(|(start, ..)| *start);
+        // Between two consecutive boundaries of either set of runs the values are constant
+        let mut bounds = want
+            .iter()
+            .chain(&have)
+            .flat_map(|(start, end, _)| [*start, *end])
+            .collect::<Vec<_>>();
+        bounds.sort_unstable();
+        bounds.dedup();
+
+        let mut pending: Option<Run<Option<ScalarValue>>> = None;
+        for range in bounds.windows(2) {
+            let (start, end) = (range[0], range[1]);
+            let value = value_at(&want, start);
+            if value == value_at(&have, start) {
+                apply_mark(tx, obj, &name, pending.take())?;
+                continue;
+            }
+            match &mut pending {
+                Some((_, pending_end, pending_value))
+                    if *pending_end == start && pending_value.as_ref() == value =>
+                {
+                    *pending_end = end
+                }
+                _ => {
+                    let run = (start, end, value.cloned());
+                    apply_mark(tx, obj, &name, pending.replace(run))?
+                }
+            }
+        }
+        apply_mark(tx, obj, &name, pending)?;
+    }
+    Ok(())
+}
+
+/// A range of the text that a mark has the same value in.
+type Run<V = ScalarValue> = (usize, usize, V);
+
+/// The value of the run in `runs`, which are sorted and don't overlap, that covers `index`.
+fn value_at(runs: &[Run], index: usize) -> Option<&ScalarValue> {
+    let after = runs.partition_point(|(start, ..)| *start <= index);
+    match after.checked_sub(1).map(|i| &runs[i]) {
+        Some((_, end, value)) if index < *end => Some(value),
+        _ => None,
+    }
+}
+
+/// Set the mark `name` to the value of `run`, or remove it if that is [`None`].
+fn apply_mark<Tx: Transactable>(
+    tx: &mut Tx,
+    obj: &ObjId,
+    name: &str,
+    run: Option<Run<Option<ScalarValue>>>,
+) -> Result<(), AutomergeError> {
+    match run {
+        Some((start, end, Some(value))) => tx.mark(
+            obj,
+            Mark::new(name.to_owned(), value, start, end),
+            ExpandMark::default(),
+        ),
+        Some((start, end, None)) => tx.unmark(obj, name, start, end, ExpandMark::default()),
+        None => Ok(()),
+    }
+}
@@ -0,0 +1,170 @@
+use super::write_marks;
+use automerge::{
+    iter::Span as AutomergeSpan, transaction::Transactable, AutomergeError, ChangeHash, ObjId,
+    ReadDoc, ScalarValue,
+};
+use serde::{de, Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Newtype struct name that the [`crate::Serializer`] and [`crate::Deserializer`] recognize to
+/// store a [`RichText`] as an [`automerge::ObjType::Text`] object with marks.
+pub(crate) const RICH_TEXT: &str = "$serde_automerge::RichText";
+
+/// A run of text that has the same set of marks applied to it.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Span {
+    pub text: String,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub marks: BTreeMap<String, ScalarValue>,
+}
+
+impl Span {
+    pub fn new(text: impl Into<String>) -> Self {
+        Self {
+            text: text.into(),
+            marks: BTreeMap::new(),
+        }
+    }
+    pub fn with_mark(mut self, name: impl Into<String>, value: impl Into<ScalarValue>) -> Self {
+        self.marks.insert(name.into(), value.into());
+        self
+    }
+
+    pub(crate) fn from_automerge(span: AutomergeSpan) -> Option<Self> {
+        match span {
+            AutomergeSpan::Text(text, marks) => Some(Span {
+                text,
+                marks: marks
+                    .iter()
+                    .flat_map(|m| m.iter())
+                    .filter(|(_, value)| !value.is_null())
+                    .map(|(name, value)| (name.to_owned(), value.clone()))
+                    .collect(),
+            }),
+            AutomergeSpan::Block(_) => None,
+        }
+    }
+}
+
+/// Drop empty spans and join adjacent spans that have the same marks.
+pub(crate) fn normalize(spans: &mut Vec<Span>) {
+    let mut normalized: Vec<Span> = Vec::with_capacity(spans.len());
+    for span in spans.drain(..).filter(|s| !s.text.is_empty()) {
+        match normalized.last_mut() {
+            Some(last) if last.marks == span.marks => last.text.push_str(&span.text),
+            _ => normalized.push(span),
+        }
+    }
+    *spans = normalized;
+}
+
+/// Text with formatting marks, such as bold or links.
+///
+/// Through this crate a [`RichText`] is stored as an [`automerge::ObjType::Text`] object: the
+/// text is updated with minimal splices and the marks are diffed against the marks already in the
+/// document, so concurrent edits to the same text merge. Other serde formats see a plain list of
+/// [`Span`]s.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RichText {
+    pub spans: Vec<Span>,
+}
+
+impl RichText {
+    pub fn new(spans: Vec<Span>) -> Self {
+        Self { spans }
+    }
+    /// The text without any marks.
+    pub fn text(&self) -> String {
+        self.spans.iter().map(|s| s.text.as_str()).collect()
+    }
+    /// Drop empty spans and join adjacent spans that have the same marks.
+    pub fn normalize(&mut self) {
+        normalize(&mut self.spans)
+    }
+
+    /// Read the text object `obj`, ignoring any block markers.
+    pub(crate) fn read<Rx: ReadDoc>(
+        doc: &Rx,
+        obj: &ObjId,
+        heads: Option<&[ChangeHash]>,
+    ) -> Result<Self, AutomergeError> {
+        let spans = match heads {
+            Some(heads) => doc.spans_at(obj, heads)?,
+            None => doc.spans(obj)?,
+        };
+        let mut text = Self::new(spans.filter_map(Span::from_automerge).collect());
+        // Unmarked ranges show up as null marks, which leaves adjacent spans with equal marks
+        text.normalize();
+        Ok(text)
+    }
+
+    /// Update the text object `obj` to contain exactly this text and these marks.
+    pub(crate) fn write<Tx: Transactable>(
+        &self,
+        tx: &mut Tx,
+        obj: &ObjId,
+    ) -> Result<(), AutomergeError> {
+        tx.update_text(obj, self.text())?;
+        let encoding = tx.text_encoding();
+        let mut start = 0;
+        let spans = self.spans.iter().map(|span| {
+            let position = start;
+            start += crate::handle::width(&span.text, encoding);
+            (position, span)
+        });
+        write_marks(tx, obj, spans.collect::<Vec<_>>())
+    }
+}
+
+impl From<&str> for RichText {
+    fn from(text: &str) -> Self {
+        Self::new(vec![Span::new(text)])
+    }
+}
+
+impl From<String> for RichText {
+    fn from(text: String) -> Self {
+        Self::new(vec![Span::new(text)])
+    }
+}
+
+impl Serialize for RichText {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_newtype_struct(RICH_TEXT, &self.spans)
+    }
+}
+
+impl<'de> Deserialize<'de> for RichText {
+    fn deserialize<D: de::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct Visitor;
+
+        impl<'de> de::Visitor<'de> for Visitor {
+            type Value = RichText;
+
+            fn expecting(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                formatter.write_str("a string or a list of spans")
+            }
+
+            fn visit_newtype_struct<D: de::Deserializer<'de>>(
+                self,
+                deserializer: D,
+            ) -> Result<Self::Value, D::Error> {
+                deserializer.deserialize_any(self)
+            }
+
+            fn visit_str<E: de::Error>(self, v: &str) -> Result<Self::Value, E> {
+                Ok(v.into())
+            }
+
+            fn visit_seq<A: de::SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+                let mut spans = Vec::new();
+                while let Some(span) = seq.next_element()? {
+                    spans.push(span);
+                }
+                Ok(RichText::new(spans))
+            }
+        }
+
+        deserializer.deserialize_newtype_struct(RICH_TEXT, Visitor)
+    }
+}
@@ -0,0 +1,274 @@
+//! Run the sync protocol of a [`SyncSession`] over any async byte stream.
+
+use crate::{AutomergeSerdeError, SyncSession, TypedDoc};
+use automerge::sync::{Message, State};
+use serde::{de::DeserializeOwned, Serialize};
+use std::{future::Future, time::Duration};
+use tokio::{
+    io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
+    sync::{mpsc, oneshot},
+};
+
+/// Frames larger than this are rejected instead of allocating a buffer for them, or sending a
+/// length that does not fit the header.
+pub const MAX_FRAME_LEN: usize = 64 * 1024 * 1024;
+
+type Command<T> = Box<dyn FnOnce(&mut TypedDoc<T>) + Send>;
+
+/// Gives access to the document of a running sync loop, see [`connect()`].
+pub struct SyncHandle<T> {
+    commands: mpsc::UnboundedSender<Command<T>>,
+    updates: mpsc::UnboundedReceiver<Result<T, AutomergeSerdeError>>,
+}
+
+impl<T: Send + 'static> SyncHandle<T> {
+    /// Run `f` on the document inside of the sync loop and send any changes it makes to the peer.
+    pub async fn with_doc<R: Send + 'static>(
+        &self,
+        f: impl FnOnce(&mut TypedDoc<T>) -> R + Send + 'static,
+    ) -> Result<R, AutomergeSerdeError> {
+        let (tx, rx) = oneshot::channel();
+        let command: Command<T> = Box::new(move |doc| {
+            let _ = tx.send(f(doc));
+        });
+        self.commands
+            .send(command)
+            .map_err(|_| AutomergeSerdeError::Closed)?;
+        rx.await.map_err(|_| AutomergeSerdeError::Closed)
+    }
+    /// The next value of the document after a message from the peer changed it, or [`None`] once
+    /// the sync loop stopped.
+    ///
+    /// Is an error if the changed document does not deserialize into `T`, which does not stop
+    /// the sync loop.
+    pub async fn next_update(&mut self) -> Option<Result<T, AutomergeSerdeError>> {
+        self.updates.recv().await
+    }
+}
+
+impl<T: Serialize + DeserializeOwned + Send + 'static> SyncHandle<T> {
+    pub async fn read(&self) -> Result<T, AutomergeSerdeError> {
+        self.with_doc(|doc| doc.read()).await?
+    }
+    /// See [`TypedDoc::modify()`].
+    pub async fn modify(
+        &self,
+        f: impl FnOnce(&mut T) + Send + 'static,
+    ) -> Result<(), AutomergeSerdeError> {
+        self.with_doc(|doc| doc.modify(f)).await?
+    }
+}
+
+async fn read_frame<R: AsyncRead + Unpin>(
+    reader: &mut R,
+) -> Result<Option<Vec<u8>>, AutomergeSerdeError> {
+    let len = match reader.read_u32().await {
+        Ok(len) => len as usize,
+        Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+    if len > MAX_FRAME_LEN {
+        return Err(AutomergeSerdeError::FrameTooLarge(len));
+    }
+    let mut frame = vec![0; len];
+    reader.read_exact(&mut frame).await?;
+    Ok(Some(frame))
+}
+
+async fn send_messages<T: Serialize + DeserializeOwned, W: AsyncWrite + Unpin>(
+    session: &mut SyncSession<T>,
+    writer: &mut W,
+) -> Result<(), AutomergeSerdeError> {
+    while let Some(message) = session.generate_message() {
+        let frame = message.encode();
+        if frame.len() > MAX_FRAME_LEN {
+            return Err(AutomergeSerdeError::FrameTooLarge(frame.len()));
+        }
+        writer.write_u32(frame.len() as u32).await?;
+        writer.write_all(&frame).await?;
+    }
+    writer.flush().await?;
+    Ok(())
+}
+
+/// Sync `session` with a peer over `io`, framing every message with its length as a big-endian
+/// `u32`.
+///
+/// Returns a handle to read and modify the document and a future that runs the sync loop, which
+/// has to be polled or spawned for anything to happen. The future resolves with the session once
+/// the peer closes the stream, so that its sync state can be reused for the next connection. Use
+/// [`reconnect()`] to keep syncing when the connection drops.
+pub fn connect<T, Io>(
+    mut session: SyncSession<T>,
+    io: Io,
+) -> (
+    SyncHandle<T>,
+    impl Future<Output = Result<SyncSession<T>, AutomergeSerdeError>>,
+)
+where
+    T: Serialize + DeserializeOwned + Send + 'static,
+    Io: AsyncRead + AsyncWrite,
+{
+    let (handle, mut commands, updates) = handle();
+    let run = async move {
+        run(&mut session, io, &mut commands, &updates).await?;
+        Ok(session)
+    };
+    (handle, run)
+}
+
+/// How long [`reconnect()`] waits before connecting again, doubling the delay after every failed
+/// attempt up to a maximum.
+#[derive(Debug, Clone)]
+pub struct Backoff {
+    initial: Duration,
+    max: Duration,
+    max_attempts: Option<usize>,
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Self::new(Duration::from_millis(100), Duration::from_secs(30))
+    }
+}
+
+impl Backoff {
+    pub fn new(initial: Duration, max: Duration) -> Self {
+        Self {
+            initial,
+            max,
+            max_attempts: None,
+        }
+    }
+    /// Give up once this many attempts in a row failed, instead of retrying forever.
+    pub fn with_max_attempts(mut self, max_attempts: usize) -> Self {
+        self.max_attempts = Some(max_attempts);
+        self
+    }
+    fn delay(&self, failures: usize) -> Duration {
+        let factor = 1u32.checked_shl(failures as u32).unwrap_or(u32::MAX);
+        self.initial.saturating_mul(factor).min(self.max)
+    }
+}
+
+/// Like [`connect()`], but opens a new stream with `open` whenever the connection fails or is
+/// closed, waiting according to `backoff` between attempts.
+///
+/// The sync state is reset for every connection, and commands sent through the handle while there
+/// is no connection wait for the next one. The future resolves with the session
+/// once the handle is dropped and the current connection ends, or with the last error once
+/// `backoff` gives up.
+pub fn reconnect<T, Io, F, Fut>(
+    mut session: SyncSession<T>,
+    mut open: F,
+    backoff: Backoff,
+) -> (
+    SyncHandle<T>,
+    impl Future<Output = Result<SyncSession<T>, AutomergeSerdeError>>,
+)
+where
+    T: Serialize + DeserializeOwned + Send + 'static,
+    Io: AsyncRead + AsyncWrite,
+    F: FnMut() -> Fut,
+    Fut: Future<Output = std::io::Result<Io>>,
+{
+    let (handle, mut commands, updates) = handle();
+    let run = async move {
+        let mut failures = 0;
+        loop {
+            let result = match open().await {
+                Ok(io) => run(&mut session, io, &mut commands, &updates).await,
+                Err(e) => Err(e.into()),
+            };
+            if updates.is_closed() {
+                return result.map(|()| session);
+            }
+            match result {
+                Ok(()) => failures = 0,
+                Err(e) => {
+                    failures += 1;
+                    if backoff.max_attempts.is_some_and(|max| failures >= max) {
+                        return Err(e);
+                    }
+                }
+            }
+            // Only what both peers are known to have carries over to the next connection
+            let (doc, state) = session.into_parts();
+            let state = State {
+                shared_heads: state.shared_heads,
+                ..State::new()
+            };
+            session = SyncSession::with_state(doc, state);
+            tokio::time::sleep(backoff.delay(failures)).await;
+        }
+    };
+    (handle, run)
+}
+
+type Updates<T> = mpsc::UnboundedSender<Result<T, AutomergeSerdeError>>;
+
+fn handle<T>() -> (
+    SyncHandle<T>,
+    mpsc::UnboundedReceiver<Command<T>>,
+    Updates<T>,
+) {
+    let (commands_tx, commands) = mpsc::unbounded_channel();
+    let (updates, updates_rx) = mpsc::unbounded_channel();
+    let handle = SyncHandle {
+        commands: commands_tx,
+        updates: updates_rx,
+    };
+    (handle, commands, updates)
+}
+
+async fn run<T, Io>(
+    session: &mut SyncSession<T>,
+    io: Io,
+    commands: &mut mpsc::UnboundedReceiver<Command<T>>,
+    updates: &Updates<T>,
+) -> Result<(), AutomergeSerdeError>
+where
+    T: Serialize + DeserializeOwned + Send + 'static,
+    Io: AsyncRead + AsyncWrite,
+{
+    let (mut reader, mut writer) = tokio::io::split(io);
+    let (frames_tx, mut frames) = mpsc::unbounded_channel();
+
+    // Reading a frame is not cancel safe, so frames are read separately from the loop below
+    let read = async move {
+        while let Some(frame) = read_frame(&mut reader).await? {
+            if frames_tx.send(frame).is_err() {
+                break;
+            }
+        }
+        drop(frames_tx);
+        std::future::pending::<Result<(), AutomergeSerdeError>>().await
+    };
+    let sync = async move {
+        let mut commands_open = true;
+        send_messages(session, &mut writer).await?;
+        loop {
+            tokio::select! {
+                frame = frames.recv() => match frame {
+                    Some(frame) => {
+                        let changed = session.receive_message(Message::decode(&frame)?)?;
+                        if !changed.is_empty() {
+                            // The handle may have been dropped, which does not stop syncing
+                            let _ = updates.send(session.read());
+                        }
+                    }
+                    None => return Ok(()),
+                },
+                command = commands.recv(), if commands_open => match command {
+                    Some(command) => command(session.doc_mut()),
+                    None => commands_open = false,
+                },
+            }
+            send_messages(session, &mut writer).await?;
+        }
+    };
+    tokio::select! {
+        Err(e) = read => Err(e),
+        result = sync => result,
+    }
+}
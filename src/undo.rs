@@ -0,0 +1,597 @@
+use crate::{value::Node, AutomergeSerdeError};
+use automerge::{
+    marks::{ExpandMark, Mark},
+    patches::TextRepresentation,
+    transaction::Transactable,
+    AutoCommit, Automerge, AutomergeError, ChangeHash, Cursor, ObjId, ObjType, PatchAction, Prop,
+    ReadDoc, ScalarValue, TextEncoding,
+};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Where an [`Inverse`] writes: a key of a map, or the element of a list or text under a cursor.
+#[derive(Debug, Clone)]
+enum Key {
+    Map(String),
+    Seq(Cursor),
+}
+
+/// One op that reverts part of a recorded change.
+#[derive(Debug, Clone)]
+enum Inverse {
+    /// Put `value` back at `key` of `obj`, or delete it.
+    Put {
+        obj: ObjId,
+        key: Key,
+        value: Option<Node>,
+    },
+    /// Delete the element of `obj` under `cursor`, which is `width` long in text.
+    Delete {
+        obj: ObjId,
+        cursor: Cursor,
+        width: usize,
+    },
+    /// Insert `values` into the list `obj` in front of the element under `before`.
+    Insert {
+        obj: ObjId,
+        before: Cursor,
+        values: Vec<Node>,
+    },
+    /// Insert `text` into `obj` in front of the element under `before`, with `marks` relative to
+    /// the start of `text`.
+    SpliceText {
+        obj: ObjId,
+        before: Cursor,
+        text: String,
+        marks: Vec<Mark<'static>>,
+    },
+    /// Add `by` to the counter at `key`.
+    Increment { obj: ObjId, key: Key, by: i64 },
+    /// Set the mark `name` from the element under `start` through the element under `end`, which
+    /// is `end_width` long, or remove it if `value` is null.
+    Mark {
+        obj: ObjId,
+        start: Cursor,
+        end: Cursor,
+        end_width: usize,
+        name: String,
+        value: ScalarValue,
+    },
+}
+
+/// An element of a list or text while walking the patches that revert a change.
+enum Elem {
+    /// An element that is in the document now, at `index`.
+    Kept { index: usize, width: usize },
+    /// A list element that the revert puts back.
+    Value(Node),
+    /// A character that the revert puts back, with its marks.
+    Char {
+        c: char,
+        width: usize,
+        marks: Vec<(String, ScalarValue)>,
+    },
+}
+
+impl Elem {
+    fn width(&self) -> usize {
+        match self {
+            Self::Kept { width, .. } | Self::Char { width, .. } => *width,
+            Self::Value(_) => 1,
+        }
+    }
+}
+
+/// The length of `c` in the indices of `encoding`.
+fn width(encoding: TextEncoding, c: char) -> usize {
+    match encoding {
+        TextEncoding::Utf8CodeUnit => c.len_utf8(),
+        TextEncoding::Utf16CodeUnit => c.len_utf16(),
+        _ => 1,
+    }
+}
+
+/// The elements of a list or text that the patches have touched so far.
+struct Seq {
+    text: bool,
+    elems: Vec<Elem>,
+}
+
+impl Seq {
+    fn new(
+        doc: &Automerge,
+        obj: &ObjId,
+        heads: &[ChangeHash],
+        encoding: TextEncoding,
+    ) -> Result<Self, AutomergeError> {
+        if doc.object_type(obj)? == ObjType::Text {
+            let mut index = 0;
+            let elems = doc
+                .text_at(obj, heads)?
+                .chars()
+                .map(|c| {
+                    let width = width(encoding, c);
+                    index += width;
+                    Elem::Kept {
+                        index: index - width,
+                        width,
+                    }
+                })
+                .collect();
+            Ok(Self { text: true, elems })
+        } else {
+            let elems = (0..doc.length_at(obj, heads))
+                .map(|index| Elem::Kept { index, width: 1 })
+                .collect();
+            Ok(Self { text: false, elems })
+        }
+    }
+
+    /// The position in `elems` of the element that starts at `index`.
+    fn locate(&self, index: usize) -> usize {
+        let mut at = 0;
+        for (i, elem) in self.elems.iter().enumerate() {
+            if at >= index {
+                return i;
+            }
+            at += elem.width();
+        }
+        self.elems.len()
+    }
+}
+
+/// Merge the marks of consecutive characters into marks relative to the first one.
+fn char_marks(chars: &[Elem]) -> Vec<Mark<'static>> {
+    let mut open = BTreeMap::<&str, (&ScalarValue, usize)>::new();
+    let mut marks = Vec::new();
+    let mut index = 0;
+    for elem in chars {
+        let Elem::Char {
+            width, marks: on, ..
+        } = elem
+        else {
+            continue;
+        };
+        open.retain(|name, (value, start)| {
+            let keep = on.iter().any(|(n, v)| n == name && v == *value);
+            if !keep {
+                marks.push(Mark::new(name.to_string(), (*value).clone(), *start, index));
+            }
+            keep
+        });
+        for (name, value) in on {
+            open.entry(name).or_insert((value, index));
+        }
+        index += width;
+    }
+    for (name, (value, start)) in open {
+        marks.push(Mark::new(name.to_owned(), value.clone(), start, index));
+    }
+    marks
+}
+
+/// The ops that turn the document at `from` back into the document at `to`.
+///
+/// List and text elements are addressed by cursor rather than by index, so that the ops still
+/// find them after concurrent changes, and only the elements that were inserted are removed.
+fn inverses(
+    doc: &Automerge,
+    from: &[ChangeHash],
+    to: &[ChangeHash],
+) -> Result<Vec<Inverse>, AutomergeError> {
+    let encoding = doc.text_encoding();
+    let cursor = |obj: &ObjId, index: usize| doc.get_cursor(obj, index, Some(from));
+    let mut ops = Vec::new();
+    let mut seqs = BTreeMap::<ObjId, Seq>::new();
+    // Objects that are put back as a whole, so the patches inside them are already covered
+    let mut restored = BTreeSet::new();
+
+    for patch in doc.diff(from, to, TextRepresentation::String(encoding)) {
+        if restored.contains(&patch.obj) || patch.path.iter().any(|(o, _)| restored.contains(o)) {
+            continue;
+        }
+        let obj = patch.obj;
+        let mut read = |(value, id): (automerge::Value<'_>, ObjId)| {
+            let node = Node::read(doc, value, &id, Some(to));
+            restored.insert(id);
+            node
+        };
+        match patch.action {
+            PatchAction::PutMap { key, value, .. } => ops.push(Inverse::Put {
+                obj,
+                key: Key::Map(key),
+                value: Some(read(value)?),
+            }),
+            PatchAction::DeleteMap { key } => ops.push(Inverse::Put {
+                obj,
+                key: Key::Map(key),
+                value: None,
+            }),
+            PatchAction::Increment {
+                prop: Prop::Map(key),
+                value,
+            } => ops.push(Inverse::Increment {
+                obj,
+                key: Key::Map(key),
+                by: value,
+            }),
+            PatchAction::Conflict { .. } => {}
+            action => {
+                if !seqs.contains_key(&obj) {
+                    seqs.insert(obj.clone(), Seq::new(doc, &obj, from, encoding)?);
+                }
+                let seq = seqs.get_mut(&obj).expect("just inserted");
+                match action {
+                    PatchAction::PutSeq { index, value, .. } => {
+                        let node = read(value)?;
+                        let at = seq.locate(index);
+                        match &mut seq.elems[at] {
+                            Elem::Kept { index, .. } => ops.push(Inverse::Put {
+                                key: Key::Seq(cursor(&obj, *index)?),
+                                obj,
+                                value: Some(node),
+                            }),
+                            elem => *elem = Elem::Value(node),
+                        }
+                    }
+                    PatchAction::Insert { index, values } => {
+                        let at = seq.locate(index);
+                        let values = values
+                            .iter()
+                            .map(|(value, id, _)| {
+                                read((value.clone(), id.clone())).map(Elem::Value)
+                            })
+                            .collect::<Result<Vec<_>, _>>()?;
+                        seq.elems.splice(at..at, values);
+                    }
+                    PatchAction::SpliceText {
+                        index,
+                        value,
+                        marks,
+                    } => {
+                        let at = seq.locate(index);
+                        let marks = marks
+                            .iter()
+                            .flat_map(|marks| marks.iter())
+                            .map(|(name, value)| (name.to_owned(), value.clone()))
+                            .collect::<Vec<_>>();
+                        let chars = value
+                            .make_string()
+                            .chars()
+                            .map(|c| Elem::Char {
+                                c,
+                                width: width(encoding, c),
+                                marks: marks.clone(),
+                            })
+                            .collect::<Vec<_>>();
+                        seq.elems.splice(at..at, chars);
+                    }
+                    PatchAction::DeleteSeq { index, length } => {
+                        let (start, end) = (seq.locate(index), seq.locate(index + length));
+                        for elem in seq.elems.drain(start..end) {
+                            if let Elem::Kept { index, width } = elem {
+                                ops.push(Inverse::Delete {
+                                    obj: obj.clone(),
+                                    cursor: cursor(&obj, index)?,
+                                    width,
+                                });
+                            }
+                        }
+                    }
+                    PatchAction::Increment {
+                        prop: Prop::Seq(index),
+                        value,
+                    } => {
+                        // A counter inside a value that is put back already has its old value
+                        if let Elem::Kept { index, .. } = seq.elems[seq.locate(index)] {
+                            ops.push(Inverse::Increment {
+                                key: Key::Seq(cursor(&obj, index)?),
+                                obj,
+                                by: value,
+                            });
+                        }
+                    }
+                    PatchAction::Mark { marks } => {
+                        for mark in marks {
+                            let (start, end) = (seq.locate(mark.start), seq.locate(mark.end));
+                            let mut runs = Vec::<(usize, usize, usize)>::new();
+                            let mut in_run = false;
+                            for elem in &mut seq.elems[start..end] {
+                                match elem {
+                                    Elem::Kept { index, width } => match runs.last_mut() {
+                                        Some(run) if in_run => (run.1, run.2) = (*index, *width),
+                                        _ => runs.push((*index, *index, *width)),
+                                    },
+                                    Elem::Char { marks, .. } => {
+                                        marks.retain(|(name, _)| name != mark.name());
+                                        if !mark.value().is_null() {
+                                            marks.push((
+                                                mark.name().to_owned(),
+                                                mark.value().clone(),
+                                            ));
+                                        }
+                                    }
+                                    Elem::Value(_) => {}
+                                }
+                                in_run = matches!(elem, Elem::Kept { .. });
+                            }
+                            for (first, last, end_width) in runs {
+                                ops.push(Inverse::Mark {
+                                    obj: obj.clone(),
+                                    start: cursor(&obj, first)?,
+                                    end: cursor(&obj, last)?,
+                                    end_width,
+                                    name: mark.name().to_owned(),
+                                    value: mark.value().clone(),
+                                });
+                            }
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    // Put back what the change removed in front of the element that follows it
+    for (obj, seq) in seqs {
+        let mut run = Vec::new();
+        for elem in seq.elems.into_iter().map(Some).chain([None]) {
+            let before = match elem {
+                Some(Elem::Kept { index, .. }) => cursor(&obj, index)?,
+                Some(elem) => {
+                    run.push(elem);
+                    continue;
+                }
+                None => Cursor::End,
+            };
+            if run.is_empty() {
+                continue;
+            }
+            if seq.text {
+                ops.push(Inverse::SpliceText {
+                    obj: obj.clone(),
+                    before,
+                    text: run
+                        .iter()
+                        .filter_map(|elem| match elem {
+                            Elem::Char { c, .. } => Some(*c),
+                            _ => None,
+                        })
+                        .collect(),
+                    marks: char_marks(&run),
+                });
+                run.clear();
+            } else {
+                ops.push(Inverse::Insert {
+                    obj: obj.clone(),
+                    before,
+                    values: run
+                        .drain(..)
+                        .filter_map(|elem| match elem {
+                            Elem::Value(node) => Some(node),
+                            _ => None,
+                        })
+                        .collect(),
+                });
+            }
+        }
+    }
+    Ok(ops)
+}
+
+/// The index of the element under `cursor`, or [`None`] if it has been deleted.
+fn position(
+    doc: &AutoCommit,
+    obj: &ObjId,
+    cursor: &Cursor,
+) -> Result<Option<usize>, AutomergeError> {
+    let index = doc.get_cursor_position(obj, cursor, None)?;
+    Ok((index < doc.length(obj) && doc.get_cursor(obj, index, None)? == *cursor).then_some(index))
+}
+
+fn apply(doc: &mut AutoCommit, inverse: Inverse) -> Result<(), AutomergeError> {
+    match inverse {
+        Inverse::Put {
+            obj,
+            key: Key::Map(key),
+            value,
+        } => match value {
+            Some(node) => node.write(doc, &obj, key.into(), false)?,
+            None => {
+                if doc.get(&obj, key.as_str())?.is_some() {
+                    doc.delete(&obj, key)?;
+                }
+            }
+        },
+        Inverse::Put {
+            obj,
+            key: Key::Seq(cursor),
+            value,
+        } => {
+            // The element may have been removed by a concurrent change, in which case there is
+            // nothing left to restore
+            if let Some(index) = position(doc, &obj, &cursor)? {
+                match value {
+                    Some(node) => node.write(doc, &obj, index.into(), false)?,
+                    None => doc.delete(&obj, index)?,
+                }
+            }
+        }
+        Inverse::Delete { obj, cursor, width } => {
+            if let Some(index) = position(doc, &obj, &cursor)? {
+                if doc.object_type(&obj)? == ObjType::Text {
+                    doc.splice_text(&obj, index, width as isize, "")?;
+                } else {
+                    doc.delete(&obj, index)?;
+                }
+            }
+        }
+        Inverse::Insert {
+            obj,
+            before,
+            values,
+        } => {
+            let index = doc.get_cursor_position(&obj, &before, None)?;
+            for (i, node) in values.iter().enumerate() {
+                node.write(doc, &obj, (index + i).into(), true)?;
+            }
+        }
+        Inverse::SpliceText {
+            obj,
+            before,
+            text,
+            marks,
+        } => {
+            let index = doc.get_cursor_position(&obj, &before, None)?;
+            doc.splice_text(&obj, index, 0, &text)?;
+            for mark in marks {
+                let (start, end) = (index + mark.start, index + mark.end);
+                let mark = Mark::new(mark.name().to_owned(), mark.value().clone(), start, end);
+                doc.mark(&obj, mark, ExpandMark::default())?;
+            }
+        }
+        Inverse::Increment { obj, key, by } => {
+            let prop: Prop = match key {
+                Key::Map(key) => key.into(),
+                Key::Seq(cursor) => match position(doc, &obj, &cursor)? {
+                    Some(index) => index.into(),
+                    None => return Ok(()),
+                },
+            };
+            doc.increment(&obj, prop, by)?;
+        }
+        Inverse::Mark {
+            obj,
+            start,
+            end,
+            end_width,
+            name,
+            value,
+        } => {
+            let start = doc.get_cursor_position(&obj, &start, None)?;
+            let end = match position(doc, &obj, &end)? {
+                Some(index) => index + end_width,
+                None => doc.get_cursor_position(&obj, &end, None)?,
+            };
+            if start < end {
+                if value.is_null() {
+                    doc.unmark(&obj, &name, start, end, ExpandMark::default())?;
+                } else {
+                    doc.mark(
+                        &obj,
+                        Mark::new(name, value, start, end),
+                        ExpandMark::default(),
+                    )?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Records local changes and reverts them with new changes, so that concurrent changes made by
+/// other peers survive an undo.
+///
+/// For every recorded change the ops that revert it are stored: puts of the previous values, with
+/// their counter and timestamp types, deletes of inserted list elements and text, and inserts of
+/// the removed ones. List elements are found by cursor, so that elements inserted concurrently by
+/// other peers stay where they are. Redoing reverts the undo the same way.
+#[derive(Debug, Default)]
+pub struct UndoManager {
+    undo: Vec<Vec<Inverse>>,
+    redo: Vec<Vec<Inverse>>,
+}
+
+impl UndoManager {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+    pub fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+    }
+
+    /// Record the local changes between `before` and `after` as one undo step, which clears the
+    /// redo history.
+    pub fn record(
+        &mut self,
+        doc: &Automerge,
+        before: &[ChangeHash],
+        after: &[ChangeHash],
+    ) -> Result<(), AutomergeSerdeError> {
+        self.push(inverses(doc, after, before)?);
+        Ok(())
+    }
+    /// Let `f` make changes to `doc`, commit them and record them as one undo step.
+    ///
+    /// If `f` fails its changes are rolled back and nothing is recorded.
+    pub fn transact<R, E: From<AutomergeError>>(
+        &mut self,
+        doc: &mut AutoCommit,
+        f: impl FnOnce(&mut AutoCommit) -> Result<R, E>,
+    ) -> Result<R, E> {
+        doc.commit();
+        let before = doc.get_heads();
+        let result = f(doc);
+        if result.is_err() {
+            doc.rollback();
+            return result;
+        }
+        doc.commit();
+        let after = doc.get_heads();
+        self.push(inverses(doc.document(), &after, &before)?);
+        result
+    }
+    fn push(&mut self, step: Vec<Inverse>) {
+        if !step.is_empty() {
+            self.undo.push(step);
+            self.redo.clear();
+        }
+    }
+
+    /// Revert the last recorded change, returns `false` if there was nothing to undo.
+    ///
+    /// A step that fails to apply, e.g. because the objects it reverts are not in `doc`, stays on
+    /// the stack, and so does a redo step that fails.
+    pub fn undo(&mut self, doc: &mut AutoCommit) -> Result<bool, AutomergeSerdeError> {
+        let Some(step) = self.undo.last() else {
+            return Ok(false);
+        };
+        let redo = restore(doc, step)?;
+        self.undo.pop();
+        self.redo.push(redo);
+        Ok(true)
+    }
+    /// Reapply the last undone change, returns `false` if there was nothing to redo.
+    pub fn redo(&mut self, doc: &mut AutoCommit) -> Result<bool, AutomergeSerdeError> {
+        let Some(step) = self.redo.last() else {
+            return Ok(false);
+        };
+        let undo = restore(doc, step)?;
+        self.redo.pop();
+        self.undo.push(undo);
+        Ok(true)
+    }
+}
+
+/// Apply `step` to `doc` as a new change and return the ops that revert it.
+fn restore(doc: &mut AutoCommit, step: &[Inverse]) -> Result<Vec<Inverse>, AutomergeError> {
+    doc.commit();
+    let before = doc.get_heads();
+    if let Err(e) = step
+        .iter()
+        .try_for_each(|inverse| apply(doc, inverse.clone()))
+    {
+        doc.rollback();
+        return Err(e);
+    }
+    doc.commit();
+    let after = doc.get_heads();
+    inverses(doc.document(), &after, &before)
+}
@@ -0,0 +1,672 @@
+use crate::{
+    de::Error,
+    ser,
+    text::{BlockText, RichText},
+};
+use automerge::{
+    transaction::Transactable, AutomergeError, ChangeHash, ObjId, ObjType, Prop, ReadDoc,
+    ScalarValue, Value,
+};
+use serde::{
+    de::{
+        self,
+        value::{MapAccessDeserializer, MapDeserializer, SeqDeserializer},
+        IntoDeserializer,
+    },
+    forward_to_deserialize_any,
+    ser::SerializeMap as _,
+    Deserialize, Serialize,
+};
+use std::{collections::BTreeMap, fmt};
+
+/// A dynamically typed value, as read from the document by the [`Deserializer`][crate::Deserializer].
+#[derive(Debug, Clone, PartialEq)]
+pub enum FieldValue {
+    Null,
+    Bool(bool),
+    Int(i64),
+    Uint(u64),
+    F64(f64),
+    Str(String),
+    Bytes(Vec<u8>),
+    List(Vec<FieldValue>),
+    Map(BTreeMap<String, FieldValue>),
+}
+
+impl<'de> Deserialize<'de> for FieldValue {
+    fn deserialize<D: de::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct Visitor;
+
+        impl<'de> de::Visitor<'de> for Visitor {
+            type Value = FieldValue;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+                formatter.write_str("any value")
+            }
+
+            fn visit_unit<E: de::Error>(self) -> Result<Self::Value, E> {
+                Ok(FieldValue::Null)
+            }
+            fn visit_none<E: de::Error>(self) -> Result<Self::Value, E> {
+                Ok(FieldValue::Null)
+            }
+            fn visit_some<D: de::Deserializer<'de>>(self, d: D) -> Result<Self::Value, D::Error> {
+                d.deserialize_any(self)
+            }
+            fn visit_bool<E: de::Error>(self, v: bool) -> Result<Self::Value, E> {
+                Ok(FieldValue::Bool(v))
+            }
+            fn visit_i64<E: de::Error>(self, v: i64) -> Result<Self::Value, E> {
+                Ok(FieldValue::Int(v))
+            }
+            fn visit_u64<E: de::Error>(self, v: u64) -> Result<Self::Value, E> {
+                Ok(FieldValue::Uint(v))
+            }
+            fn visit_f64<E: de::Error>(self, v: f64) -> Result<Self::Value, E> {
+                Ok(FieldValue::F64(v))
+            }
+            fn visit_str<E: de::Error>(self, v: &str) -> Result<Self::Value, E> {
+                Ok(FieldValue::Str(v.to_owned()))
+            }
+            fn visit_string<E: de::Error>(self, v: String) -> Result<Self::Value, E> {
+                Ok(FieldValue::Str(v))
+            }
+            fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<Self::Value, E> {
+                Ok(FieldValue::Bytes(v.to_owned()))
+            }
+            fn visit_byte_buf<E: de::Error>(self, v: Vec<u8>) -> Result<Self::Value, E> {
+                Ok(FieldValue::Bytes(v))
+            }
+            fn visit_seq<A: de::SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+                let mut values = Vec::new();
+                while let Some(value) = seq.next_element()? {
+                    values.push(value);
+                }
+                Ok(FieldValue::List(values))
+            }
+            fn visit_map<A: de::MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+                let mut values = BTreeMap::new();
+                while let Some((key, value)) = map.next_entry()? {
+                    values.insert(key, value);
+                }
+                Ok(FieldValue::Map(values))
+            }
+        }
+
+        deserializer.deserialize_any(Visitor)
+    }
+}
+
+impl Serialize for FieldValue {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Self::Null => serializer.serialize_unit(),
+            Self::Bool(v) => serializer.serialize_bool(*v),
+            Self::Int(v) => serializer.serialize_i64(*v),
+            Self::Uint(v) => serializer.serialize_u64(*v),
+            Self::F64(v) => serializer.serialize_f64(*v),
+            Self::Str(v) => serializer.serialize_str(v),
+            Self::Bytes(v) => serializer.serialize_bytes(v),
+            Self::List(v) => v.serialize(serializer),
+            Self::Map(v) => v.serialize(serializer),
+        }
+    }
+}
+
+impl fmt::Display for FieldValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Null => f.write_str("null"),
+            Self::Bool(v) => write!(f, "{v}"),
+            Self::Int(v) => write!(f, "{v}"),
+            Self::Uint(v) => write!(f, "{v}"),
+            Self::F64(v) => write!(f, "{v}"),
+            Self::Str(v) => write!(f, "{v:?}"),
+            Self::Bytes(v) => write!(f, "<{} bytes>", v.len()),
+            Self::List(v) => {
+                f.write_str("[")?;
+                for (i, value) in v.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(", ")?;
+                    }
+                    write!(f, "{value}")?;
+                }
+                f.write_str("]")
+            }
+            Self::Map(v) => {
+                f.write_str("{")?;
+                for (i, (key, value)) in v.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(", ")?;
+                    }
+                    write!(f, "{key}: {value}")?;
+                }
+                f.write_str("}")
+            }
+        }
+    }
+}
+
+impl FieldValue {
+    /// Serialize `value` into a [`FieldValue`] without going through a document.
+    pub fn from_serialize<T: ?Sized + Serialize>(value: &T) -> Result<Self, ser::Error> {
+        value.serialize(ValueSerializer)
+    }
+}
+
+impl<'de> IntoDeserializer<'de, Error> for FieldValue {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+impl<'de> serde::Deserializer<'de> for FieldValue {
+    type Error = Error;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        match self {
+            Self::Null => visitor.visit_unit(),
+            Self::Bool(v) => visitor.visit_bool(v),
+            Self::Int(v) => visitor.visit_i64(v),
+            Self::Uint(v) => visitor.visit_u64(v),
+            Self::F64(v) => visitor.visit_f64(v),
+            Self::Str(v) => visitor.visit_string(v),
+            Self::Bytes(v) => visitor.visit_byte_buf(v),
+            Self::List(v) => visitor.visit_seq(SeqDeserializer::new(v.into_iter())),
+            Self::Map(v) => visitor.visit_map(MapDeserializer::new(v.into_iter())),
+        }
+    }
+
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        match self {
+            Self::Null => visitor.visit_none(),
+            value => visitor.visit_some(value),
+        }
+    }
+
+    fn deserialize_newtype_struct<V>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        // Same layout as the serializer: unit variants are strings, all others a single-key map
+        match self {
+            Self::Str(v) => visitor.visit_enum(v.into_deserializer()),
+            Self::Map(v) => visitor.visit_enum(MapAccessDeserializer::new(MapDeserializer::new(
+                v.into_iter(),
+            ))),
+            value => value.deserialize_any(visitor),
+        }
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct seq tuple
+        tuple_struct map struct identifier ignored_any
+    }
+}
+
+/// Serializes values into a [`FieldValue`], using the same layout as the [`Serializer`][crate::Serializer].
+struct ValueSerializer;
+
+macro_rules! serialize_value {
+    ($method:ident, $type:ty, $variant:ident$( as $as:ty)?) => {
+        fn $method(self, v: $type) -> Result<FieldValue, ser::Error> {
+            Ok(FieldValue::$variant((v$( as $as)?).into()))
+        }
+    };
+}
+
+impl serde::Serializer for ValueSerializer {
+    type Ok = FieldValue;
+    type Error = ser::Error;
+
+    type SerializeSeq = SeqSerializer;
+    type SerializeTuple = SeqSerializer;
+    type SerializeTupleStruct = SeqSerializer;
+    type SerializeTupleVariant = VariantSerializer<SeqSerializer>;
+    type SerializeMap = MapSerializer;
+    type SerializeStruct = MapSerializer;
+    type SerializeStructVariant = VariantSerializer<MapSerializer>;
+
+    serialize_value!(serialize_bool, bool, Bool);
+    serialize_value!(serialize_i8, i8, Int);
+    serialize_value!(serialize_i16, i16, Int);
+    serialize_value!(serialize_i32, i32, Int);
+    serialize_value!(serialize_i64, i64, Int);
+    serialize_value!(serialize_i128, i128, Int as i64);
+    serialize_value!(serialize_u8, u8, Uint);
+    serialize_value!(serialize_u16, u16, Uint);
+    serialize_value!(serialize_u32, u32, Uint);
+    serialize_value!(serialize_u64, u64, Uint);
+    serialize_value!(serialize_u128, u128, Uint as u64);
+    serialize_value!(serialize_f32, f32, F64);
+    serialize_value!(serialize_f64, f64, F64);
+    serialize_value!(serialize_char, char, Str);
+    serialize_value!(serialize_str, &str, Str);
+    serialize_value!(serialize_bytes, &[u8], Bytes);
+
+    fn serialize_none(self) -> Result<FieldValue, ser::Error> {
+        Ok(FieldValue::Null)
+    }
+
+    fn serialize_some<T: ?Sized + Serialize>(self, value: &T) -> Result<FieldValue, ser::Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<FieldValue, ser::Error> {
+        Ok(FieldValue::Null)
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<FieldValue, ser::Error> {
+        Ok(FieldValue::Null)
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<FieldValue, ser::Error> {
+        Ok(FieldValue::Str(variant.to_owned()))
+    }
+
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<FieldValue, ser::Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<FieldValue, ser::Error> {
+        Ok(self::variant(variant, value.serialize(self)?))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<SeqSerializer, ser::Error> {
+        Ok(SeqSerializer(Vec::with_capacity(len.unwrap_or_default())))
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<SeqSerializer, ser::Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<SeqSerializer, ser::Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleVariant, ser::Error> {
+        Ok(VariantSerializer {
+            variant,
+            inner: self.serialize_seq(Some(len))?,
+        })
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<MapSerializer, ser::Error> {
+        Ok(MapSerializer::default())
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<MapSerializer, ser::Error> {
+        self.serialize_map(Some(len))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeStructVariant, ser::Error> {
+        Ok(VariantSerializer {
+            variant,
+            inner: self.serialize_map(Some(len))?,
+        })
+    }
+}
+
+struct SeqSerializer(Vec<FieldValue>);
+
+impl serde::ser::SerializeSeq for SeqSerializer {
+    type Ok = FieldValue;
+    type Error = ser::Error;
+
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), ser::Error> {
+        self.0.push(value.serialize(ValueSerializer)?);
+        Ok(())
+    }
+
+    fn end(self) -> Result<FieldValue, ser::Error> {
+        Ok(FieldValue::List(self.0))
+    }
+}
+
+impl serde::ser::SerializeTuple for SeqSerializer {
+    type Ok = FieldValue;
+    type Error = ser::Error;
+
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), ser::Error> {
+        serde::ser::SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<FieldValue, ser::Error> {
+        serde::ser::SerializeSeq::end(self)
+    }
+}
+
+impl serde::ser::SerializeTupleStruct for SeqSerializer {
+    type Ok = FieldValue;
+    type Error = ser::Error;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), ser::Error> {
+        serde::ser::SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<FieldValue, ser::Error> {
+        serde::ser::SerializeSeq::end(self)
+    }
+}
+
+#[derive(Default)]
+struct MapSerializer {
+    map: BTreeMap<String, FieldValue>,
+    next_key: Option<String>,
+}
+
+impl serde::ser::SerializeMap for MapSerializer {
+    type Ok = FieldValue;
+    type Error = ser::Error;
+
+    fn serialize_key<T: ?Sized + Serialize>(&mut self, key: &T) -> Result<(), ser::Error> {
+        self.next_key = Some(key.serialize(ser::KeySerializer)?);
+        Ok(())
+    }
+
+    fn serialize_value<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), ser::Error> {
+        let key = self
+            .next_key
+            .take()
+            .expect("serialize_value called before serialize_key");
+        self.map.insert(key, value.serialize(ValueSerializer)?);
+        Ok(())
+    }
+
+    fn end(self) -> Result<FieldValue, ser::Error> {
+        Ok(FieldValue::Map(self.map))
+    }
+}
+
+impl serde::ser::SerializeStruct for MapSerializer {
+    type Ok = FieldValue;
+    type Error = ser::Error;
+
+    fn serialize_field<T: ?Sized + Serialize>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), ser::Error> {
+        self.serialize_entry(key, value)
+    }
+
+    fn end(self) -> Result<FieldValue, ser::Error> {
+        serde::ser::SerializeMap::end(self)
+    }
+}
+
+/// Variants with a value are stored as a map with the variant as its only key.
+fn variant(variant: &'static str, value: FieldValue) -> FieldValue {
+    FieldValue::Map(BTreeMap::from([(variant.to_owned(), value)]))
+}
+
+struct VariantSerializer<S> {
+    variant: &'static str,
+    inner: S,
+}
+
+impl serde::ser::SerializeTupleVariant for VariantSerializer<SeqSerializer> {
+    type Ok = FieldValue;
+    type Error = ser::Error;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), ser::Error> {
+        serde::ser::SerializeSeq::serialize_element(&mut self.inner, value)
+    }
+
+    fn end(self) -> Result<FieldValue, ser::Error> {
+        let value = serde::ser::SerializeSeq::end(self.inner)?;
+        Ok(variant(self.variant, value))
+    }
+}
+
+impl serde::ser::SerializeStructVariant for VariantSerializer<MapSerializer> {
+    type Ok = FieldValue;
+    type Error = ser::Error;
+
+    fn serialize_field<T: ?Sized + Serialize>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), ser::Error> {
+        self.inner.serialize_entry(key, value)
+    }
+
+    fn end(self) -> Result<FieldValue, ser::Error> {
+        let value = serde::ser::SerializeMap::end(self.inner)?;
+        Ok(variant(self.variant, value))
+    }
+}
+
+/// A value read out of the document with its automerge types intact, so that it can be written
+/// back with its text, block markers, marks, counters and timestamps, which a round trip through
+/// [`FieldValue`] would flatten.
+#[derive(Debug, Clone)]
+pub(crate) enum Node {
+    Scalar(ScalarValue),
+    Map(Vec<(String, Node)>),
+    List(Vec<Node>),
+    Text(BlockText),
+}
+
+impl Node {
+    /// Read `value`, which is `obj` if it is an object, at `heads`.
+    pub(crate) fn read<Rx: ReadDoc>(
+        doc: &Rx,
+        value: Value<'_>,
+        obj: &ObjId,
+        heads: Option<&[ChangeHash]>,
+    ) -> Result<Self, AutomergeError> {
+        let get = |prop: Prop| match heads {
+            Some(heads) => doc.get_at(obj, prop, heads),
+            None => doc.get(obj, prop),
+        };
+        let read_child = |prop: Prop| match get(prop)? {
+            Some((value, id)) => Self::read(doc, value, &id, heads),
+            None => unreachable!("the property was just listed"),
+        };
+        Ok(match value {
+            Value::Scalar(s) => Self::Scalar(s.into_owned()),
+            Value::Object(ObjType::Map | ObjType::Table) => {
+                let keys = match heads {
+                    Some(heads) => doc.keys_at(obj, heads).collect::<Vec<_>>(),
+                    None => doc.keys(obj).collect(),
+                };
+                Self::Map(
+                    keys.into_iter()
+                        .map(|key| Ok((key.clone(), read_child(key.into())?)))
+                        .collect::<Result<_, AutomergeError>>()?,
+                )
+            }
+            Value::Object(ObjType::List) => {
+                let length = match heads {
+                    Some(heads) => doc.length_at(obj, heads),
+                    None => doc.length(obj),
+                };
+                Self::List(
+                    (0..length)
+                        .map(|index| read_child(index.into()))
+                        .collect::<Result<_, _>>()?,
+                )
+            }
+            Value::Object(ObjType::Text) => Self::Text(BlockText::read(doc, obj, heads)?),
+        })
+    }
+
+    /// Write this value to `prop` of `obj`, inserting it if `insert` is set.
+    pub(crate) fn write<Tx: Transactable>(
+        &self,
+        tx: &mut Tx,
+        obj: &ObjId,
+        prop: Prop,
+        insert: bool,
+    ) -> Result<(), AutomergeError> {
+        let obj_type = match self {
+            Self::Scalar(s) => {
+                match prop {
+                    Prop::Seq(index) if insert => tx.insert(obj, index, s.clone())?,
+                    prop => tx.put(obj, prop, s.clone())?,
+                }
+                return Ok(());
+            }
+            Self::Map(_) => ObjType::Map,
+            Self::List(_) => ObjType::List,
+            Self::Text(_) => ObjType::Text,
+        };
+        let child = match prop {
+            Prop::Seq(index) if insert => tx.insert_object(obj, index, obj_type)?,
+            prop => tx.put_object(obj, prop, obj_type)?,
+        };
+        self.write_contents(tx, &child)
+    }
+
+    /// Whether reconciling `self` in the document with `other` leaves it unchanged, which also
+    /// holds for a plain text object and an equal string.
+    pub(crate) fn same(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::Scalar(a), Self::Scalar(b)) => a == b,
+            (Self::Text(text), Self::Scalar(ScalarValue::Str(s)))
+            | (Self::Scalar(ScalarValue::Str(s)), Self::Text(text)) => {
+                text.blocks.is_empty()
+                    && text.leading.iter().all(|span| span.marks.is_empty())
+                    && text
+                        .leading
+                        .iter()
+                        .map(|span| span.text.as_str())
+                        .collect::<String>()
+                        == *s
+            }
+            (Self::Text(a), Self::Text(b)) => a == b,
+            (Self::Map(a), Self::Map(b)) => {
+                a.len() == b.len()
+                    && a.iter()
+                        .zip(b)
+                        .all(|((ka, a), (kb, b))| ka == kb && a.same(b))
+            }
+            (Self::List(a), Self::List(b)) => {
+                a.len() == b.len() && a.iter().zip(b).all(|(a, b)| a.same(b))
+            }
+            _ => false,
+        }
+    }
+
+    /// Whether `other` is likely an edited version of `self`: maps that have an entry in common,
+    /// or two values that are the [`same()`][Self::same()].
+    pub(crate) fn similar(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::Map(a), Self::Map(b)) => a
+                .iter()
+                .any(|(key, a)| b.iter().any(|(k, b)| k == key && a.same(b))),
+            _ => self.same(other),
+        }
+    }
+
+    /// Fill the empty object `obj` with the contents of this value.
+    pub(crate) fn write_contents<Tx: Transactable>(
+        &self,
+        tx: &mut Tx,
+        obj: &ObjId,
+    ) -> Result<(), AutomergeError> {
+        match self {
+            Self::Scalar(_) => unreachable!("scalars have no contents"),
+            Self::Map(entries) => {
+                for (key, node) in entries {
+                    node.write(tx, obj, key.as_str().into(), false)?;
+                }
+            }
+            Self::List(items) => {
+                for (index, node) in items.iter().enumerate() {
+                    node.write(tx, obj, index.into(), true)?;
+                }
+            }
+            Self::Text(text) => text.write(tx, obj)?,
+        }
+        Ok(())
+    }
+}
+
+/// Serializes the value the way the [`Serializer`][crate::Serializer] would write it, so that it
+/// can be reconciled into an existing value.
+impl Serialize for Node {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Self::Scalar(s) => match s {
+                ScalarValue::Bytes(v) | ScalarValue::Unknown { bytes: v, .. } => {
+                    serializer.serialize_bytes(v)
+                }
+                ScalarValue::Str(v) => serializer.serialize_str(v),
+                ScalarValue::Int(v) | ScalarValue::Timestamp(v) => serializer.serialize_i64(*v),
+                ScalarValue::Uint(v) => serializer.serialize_u64(*v),
+                ScalarValue::F64(v) => serializer.serialize_f64(*v),
+                ScalarValue::Counter(v) => serializer.serialize_i64(v.into()),
+                ScalarValue::Boolean(v) => serializer.serialize_bool(*v),
+                ScalarValue::Null => serializer.serialize_unit(),
+            },
+            Self::Map(entries) => serializer.collect_map(entries.iter().map(|(k, v)| (k, v))),
+            Self::List(items) => serializer.collect_seq(items),
+            Self::Text(text) if text.blocks.is_empty() => {
+                RichText::new(text.leading.clone()).serialize(serializer)
+            }
+            Self::Text(text) => text.serialize(serializer),
+        }
+    }
+}
@@ -0,0 +1,159 @@
+use serde::{Deserialize, Serialize};
+use serde_automerge::{
+    impl_apply_patch,
+    patch::{apply_patches, ApplyPatch},
+    AutomergeSerdeError, ObjId, Prop, ReadDoc, TypedDoc, Value,
+};
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+struct Float3 {
+    x: i32,
+    y: i32,
+    z: i32,
+}
+impl ApplyPatch for Float3 {}
+
+/// Fails the test if it is ever deserialized again after the initial read
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+struct Untouched(String);
+impl ApplyPatch for Untouched {
+    fn reload<'a, Rx: ReadDoc>(
+        &mut self,
+        _doc: &'a Rx,
+        _node: Option<(Value<'a>, ObjId)>,
+        path: &[Prop],
+    ) -> Result<(), AutomergeSerdeError> {
+        panic!("reloaded untouched value at {path:?}")
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+struct World {
+    position: Float3,
+    names: Vec<String>,
+    tags: HashMap<String, i64>,
+    label: Option<String>,
+    untouched: Untouched,
+}
+impl_apply_patch!(World {
+    position,
+    names,
+    tags,
+    label,
+    untouched
+});
+
+fn world() -> World {
+    World {
+        position: Float3 { x: 1, y: 2, z: 3 },
+        names: vec!["a".to_owned(), "b".to_owned()],
+        tags: HashMap::from([("one".to_owned(), 1), ("two".to_owned(), 2)]),
+        label: None,
+        untouched: Untouched("static".to_owned()),
+    }
+}
+
+#[test]
+fn test_refresh_after_remote_changes() {
+    let mut doc = TypedDoc::new(&world()).unwrap();
+    let mut remote = doc.fork();
+    let mut value = doc.read().unwrap();
+
+    remote
+        .modify(|w| {
+            w.position.y = 20;
+            w.names.insert(0, "z".to_owned());
+            w.names[2] = "c".to_owned();
+            w.tags.remove("one");
+            w.tags.insert("three".to_owned(), 3);
+            w.label = Some("remote".to_owned());
+        })
+        .unwrap();
+    doc.merge(&mut remote).unwrap();
+
+    doc.refresh(&mut value).unwrap();
+    assert_eq!(value, doc.read().unwrap());
+    assert_eq!(value.names, vec!["z", "a", "c"]);
+}
+
+#[test]
+fn test_apply_patches_from_diff() {
+    let mut doc = TypedDoc::new(&world()).unwrap();
+    let before = doc.get_heads();
+    let mut value = doc.read().unwrap();
+
+    doc.modify(|w| {
+        w.position.x = 6;
+        *w.tags.get_mut("two").unwrap() = 22;
+    })
+    .unwrap();
+    let after = doc.get_heads();
+
+    let patches = doc.doc_mut().diff(&before, &after);
+    apply_patches(&mut value, doc.doc(), patches).unwrap();
+    assert_eq!(value, doc.read().unwrap());
+}
+
+thread_local! {
+    static RELOADS: std::cell::Cell<usize> = const { std::cell::Cell::new(0) };
+}
+
+/// Counts how often the patch is followed into it rather than deserializing its parent again
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+struct Tracked(String);
+impl ApplyPatch for Tracked {
+    fn reload<'a, Rx: ReadDoc>(
+        &mut self,
+        doc: &'a Rx,
+        node: Option<(Value<'a>, ObjId)>,
+        _path: &[Prop],
+    ) -> Result<(), AutomergeSerdeError> {
+        RELOADS.set(RELOADS.get() + 1);
+        serde_automerge::patch::reload_all(self, doc, node)
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+struct Renamed {
+    display_name: Tracked,
+    #[serde(rename = "n")]
+    count: i64,
+}
+impl_apply_patch!(Renamed {
+    display_name as "displayName",
+    count as "n",
+});
+
+#[test]
+fn test_renamed_fields_receive_patches() {
+    let mut doc = TypedDoc::new(&Renamed {
+        display_name: Tracked("a".to_owned()),
+        count: 1,
+    })
+    .unwrap();
+    let mut value = doc.read().unwrap();
+
+    doc.modify(|r| r.display_name.0 = "b".to_owned()).unwrap();
+    doc.refresh(&mut value).unwrap();
+    assert_eq!(value.display_name.0, "b");
+    assert_eq!(RELOADS.get(), 1);
+
+    doc.modify(|r| r.count = 2).unwrap();
+    doc.refresh(&mut value).unwrap();
+    assert_eq!(value, doc.read().unwrap());
+    assert_eq!(RELOADS.get(), 1);
+}
+
+#[test]
+fn test_refresh_after_from_doc_has_no_changes() {
+    let mut doc = TypedDoc::new(&world()).unwrap();
+    let mut value = doc.read().unwrap();
+
+    let mut wrapped = TypedDoc::<World>::from_doc(doc.doc_mut().fork());
+    wrapped.refresh(&mut value).unwrap();
+    let mut forked = doc.fork();
+    forked.refresh(&mut value).unwrap();
+    assert_eq!(value, world());
+}
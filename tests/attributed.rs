@@ -0,0 +1,51 @@
+use serde::{Deserialize, Serialize};
+use serde_automerge::{
+    Attributed, AutomergeGetExtension, AutomergeSetExtension, Deserializer, TypedDoc, ROOT,
+};
+
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
+struct Card {
+    title: Attributed<String>,
+    tags: Vec<Attributed<String>>,
+    note: Option<Attributed<String>>,
+}
+
+fn card() -> Card {
+    Card {
+        title: Attributed::new("a".to_owned()),
+        tags: vec![Attributed::new("x".to_owned())],
+        note: None,
+    }
+}
+
+#[test]
+fn test_attributed_actor() {
+    let mut doc = TypedDoc::new(&card()).unwrap();
+    let mut other = doc.fork();
+    other
+        .modify(|c| c.title = Attributed::new("b".to_owned()))
+        .unwrap();
+    doc.merge(&mut other).unwrap();
+
+    let read = doc.read().unwrap();
+    assert_eq!(*read.title, "b");
+    assert_eq!(read.title.actor(), Some(other.doc().get_actor()));
+    assert_eq!(read.tags[0].actor(), Some(doc.doc().get_actor()));
+    assert!(read.tags[0].counter().is_some());
+    assert!(read.note.is_none());
+}
+
+#[test]
+fn test_attributed_missing_and_roundtrip() {
+    let mut doc = serde_automerge::AutoCommit::new();
+    doc.set_value(ROOT, "value", 5).unwrap();
+
+    let value: Attributed<i64> = doc.get_value(ROOT, "value").unwrap().unwrap();
+    assert_eq!(value.value, 5);
+    assert!(value.op.is_some());
+    let missing = Attributed::<Option<i64>>::deserialize(Deserializer::new(&doc, None)).unwrap();
+    assert_eq!(missing, Attributed::new(None));
+
+    doc.set_value(ROOT, "copy", &value).unwrap();
+    assert_eq!(doc.get_value::<i64, _>(ROOT, "copy").unwrap(), Some(5));
+}
@@ -0,0 +1,117 @@
+use serde::{Deserialize, Serialize};
+use serde_automerge::{
+    blame::{blame, field_history},
+    transaction::CommitOptions,
+    transaction::Transactable,
+    AutoCommit, AutomergeReconcileExtension, AutomergeSetExtension, Prop, ROOT,
+};
+
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+struct Profile {
+    name: String,
+    tags: Vec<String>,
+}
+
+fn name() -> Vec<Prop> {
+    vec![
+        Prop::Map("profile".to_owned()),
+        Prop::Map("name".to_owned()),
+    ]
+}
+
+fn doc() -> (AutoCommit, AutoCommit) {
+    let mut doc = AutoCommit::new();
+    let profile = Profile {
+        name: "ann".to_owned(),
+        tags: vec![],
+    };
+    doc.set_value(ROOT, "profile", &profile).unwrap();
+    doc.commit_with(CommitOptions::default().with_message("create"));
+
+    let mut other = doc.fork();
+    other
+        .reconcile_value(
+            ROOT,
+            "profile",
+            Profile {
+                name: "bob".to_owned(),
+                ..profile.clone()
+            },
+        )
+        .unwrap();
+    other.commit_with(CommitOptions::default().with_message("rename"));
+    doc.reconcile_value(
+        ROOT,
+        "profile",
+        Profile {
+            tags: vec!["x".to_owned()],
+            ..profile
+        },
+    )
+    .unwrap();
+    doc.commit_with(CommitOptions::default().with_message("tag"));
+    doc.merge(&mut other).unwrap();
+    (doc, other)
+}
+
+#[test]
+fn test_blame() {
+    let (mut doc, other) = doc();
+    let found = blame(doc.document(), &name()).unwrap().unwrap();
+    assert_eq!(&found.change.actor, other.get_actor());
+    assert_eq!(found.change.message.as_deref(), Some("rename"));
+
+    let tag = vec![
+        Prop::Map("profile".to_owned()),
+        Prop::Map("tags".to_owned()),
+        Prop::Seq(0),
+    ];
+    let found = blame(doc.document(), &tag).unwrap().unwrap();
+    assert_eq!(found.change.message.as_deref(), Some("tag"));
+
+    let missing = [Prop::Map("missing".to_owned())];
+    assert!(blame(doc.document(), &missing).unwrap().is_none());
+}
+
+#[test]
+fn test_field_history() {
+    let (mut doc, _) = doc();
+    let edits = field_history::<String>(doc.document(), &name()).unwrap();
+    let edits = edits
+        .into_iter()
+        .map(|e| (e.change.message.unwrap(), e.before, e.after))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        edits,
+        vec![
+            ("create".to_owned(), None, Some("ann".to_owned())),
+            (
+                "rename".to_owned(),
+                Some("ann".to_owned()),
+                Some("bob".to_owned())
+            ),
+        ]
+    );
+}
+
+#[test]
+fn test_field_history_of_removed_value() {
+    let (mut doc, _) = doc();
+    doc.delete(ROOT, "profile").unwrap();
+    doc.commit_with(CommitOptions::default().with_message("remove"));
+
+    let edits = field_history::<Profile>(doc.document(), &[Prop::Map("profile".to_owned())])
+        .unwrap()
+        .into_iter()
+        .map(|e| (e.change.message.unwrap(), e.after.map(|p| p.name)))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        edits,
+        vec![
+            ("create".to_owned(), Some("ann".to_owned())),
+            ("tag".to_owned(), Some("ann".to_owned())),
+            ("rename".to_owned(), Some("bob".to_owned())),
+            ("remove".to_owned(), None),
+        ]
+    );
+}
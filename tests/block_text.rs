@@ -0,0 +1,69 @@
+use serde::{Deserialize, Serialize};
+use serde_automerge::{
+    text::{Block, Span},
+    BlockText, ObjId, ReadDoc, TypedDoc,
+};
+
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
+struct Document {
+    content: BlockText,
+}
+
+fn content() -> BlockText {
+    BlockText::new(vec![
+        Block::new("heading")
+            .with_attr("level", 1)
+            .with_span(Span::new("Title")),
+        Block::new("paragraph")
+            .with_span(Span::new("Some "))
+            .with_span(Span::new("bold").with_mark("bold", true))
+            .with_span(Span::new(" text")),
+        Block::new("unordered-list-item")
+            .with_parent("unordered-list")
+            .with_span(Span::new("item")),
+    ])
+}
+
+#[test]
+fn test_block_text_roundtrip() {
+    let doc = TypedDoc::new(&Document { content: content() }).unwrap();
+    assert_eq!(doc.read().unwrap().content, content());
+
+    let (_, text) = doc.doc().get(ObjId::Root, "content").unwrap().unwrap();
+    // Block markers show up as object replacement characters in the plain text
+    assert_eq!(
+        doc.doc().text(&text).unwrap(),
+        "\u{fffc}Title\u{fffc}Some bold text\u{fffc}item"
+    );
+}
+
+#[test]
+fn test_concurrent_block_edits_merge() {
+    let mut doc = TypedDoc::new(&Document { content: content() }).unwrap();
+    let mut fork = doc.fork();
+
+    doc.modify(|d| {
+        d.content.blocks[0] = Block::new("heading")
+            .with_attr("level", 2)
+            .with_span(Span::new("Title"));
+    })
+    .unwrap();
+    fork.modify(|d| {
+        d.content.blocks[2].spans[0].text = "first item".to_owned();
+        d.content
+            .blocks
+            .push(Block::new("paragraph").with_span(Span::new("The end")));
+    })
+    .unwrap();
+
+    doc.merge(&mut fork).unwrap();
+    let mut expected = content();
+    expected.blocks[0]
+        .attrs
+        .insert("level".to_owned(), 2.into());
+    expected.blocks[2].spans[0].text = "first item".to_owned();
+    expected
+        .blocks
+        .push(Block::new("paragraph").with_span(Span::new("The end")));
+    assert_eq!(doc.read().unwrap().content, expected);
+}
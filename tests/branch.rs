@@ -0,0 +1,68 @@
+use serde::{Deserialize, Serialize};
+use serde_automerge::{AutomergeSerdeError, Branches, TypedDoc};
+
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+struct Article {
+    title: String,
+    body: String,
+    published: bool,
+}
+
+fn article() -> Article {
+    Article {
+        title: "Draft".to_owned(),
+        body: "".to_owned(),
+        published: false,
+    }
+}
+
+#[test]
+fn test_draft_then_publish() {
+    let mut branches = Branches::new(TypedDoc::new(&article()).unwrap());
+    let draft = branches.fork("draft", None).unwrap();
+    draft.modify(|a| a.body = "Hello".to_owned()).unwrap();
+    branches
+        .main_mut()
+        .modify(|a| a.title = "Final".to_owned())
+        .unwrap();
+
+    let preview = branches.preview("draft").unwrap();
+    assert_eq!(
+        preview,
+        Article {
+            title: "Final".to_owned(),
+            body: "Hello".to_owned(),
+            published: false,
+        }
+    );
+    // Previewing changes neither side
+    assert_eq!(branches.main().read().unwrap().body, "");
+    assert_eq!(
+        branches.branch("draft").unwrap().read().unwrap().title,
+        "Draft"
+    );
+
+    branches.merge("draft").unwrap();
+    assert_eq!(branches.main().read().unwrap(), preview);
+    assert_eq!(branches.names().count(), 0);
+    assert!(matches!(
+        branches.merge("draft"),
+        Err(AutomergeSerdeError::BranchNotFound(_))
+    ));
+}
+
+#[test]
+fn test_fork_at_heads() {
+    let mut branches = Branches::new(TypedDoc::new(&article()).unwrap());
+    let heads = branches.main_mut().get_heads();
+    branches.main_mut().modify(|a| a.published = true).unwrap();
+
+    let old = branches.fork("old", Some(&heads)).unwrap();
+    assert_eq!(old.read().unwrap(), article());
+    assert!(matches!(
+        branches.fork("old", None),
+        Err(AutomergeSerdeError::BranchExists(_))
+    ));
+    assert!(branches.discard("old").is_some());
+    assert_eq!(branches.names().collect::<Vec<_>>(), Vec::<&str>::new());
+}
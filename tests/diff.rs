@@ -0,0 +1,74 @@
+use serde::{Deserialize, Serialize};
+use serde_automerge::{
+    diff::{FieldChange, FieldValue},
+    Prop, TypedDoc,
+};
+
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+struct Float3 {
+    x: i32,
+    y: i32,
+    z: i32,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+struct Scene {
+    position: Float3,
+    names: Vec<String>,
+}
+
+fn scene() -> Scene {
+    Scene {
+        position: Float3 { x: 1, y: 2, z: 3 },
+        names: vec!["a".to_owned(), "b".to_owned()],
+    }
+}
+
+#[test]
+fn test_diff_between_heads() {
+    let mut doc = TypedDoc::new(&scene()).unwrap();
+    let before = doc.get_heads();
+    doc.modify(|s| {
+        s.position.x = 6;
+        s.names.push("c".to_owned());
+    })
+    .unwrap();
+    let after = doc.get_heads();
+
+    let diff = doc.diff(&before, &after).unwrap();
+    assert_eq!(diff.before, scene());
+    assert_eq!(diff.after, doc.read().unwrap());
+    assert_eq!(
+        diff.changes,
+        vec![
+            FieldChange {
+                path: vec![Prop::Map("names".into())],
+                old: Some(FieldValue::List(vec![
+                    FieldValue::Str("a".into()),
+                    FieldValue::Str("b".into()),
+                ])),
+                new: Some(FieldValue::List(vec![
+                    FieldValue::Str("a".into()),
+                    FieldValue::Str("b".into()),
+                    FieldValue::Str("c".into()),
+                ])),
+            },
+            FieldChange {
+                path: vec![Prop::Map("position".into()), Prop::Map("x".into())],
+                old: Some(FieldValue::Int(1)),
+                new: Some(FieldValue::Int(6)),
+            },
+        ]
+    );
+    assert_eq!(diff.changes[1].to_string(), "position.x: 1 → 6");
+}
+
+#[test]
+fn test_read_at_heads() {
+    let mut doc = TypedDoc::new(&scene()).unwrap();
+    let heads = doc.get_heads();
+    doc.modify(|s| s.names.clear()).unwrap();
+
+    assert_eq!(doc.read_at(&heads).unwrap(), scene());
+    assert!(doc.read().unwrap().names.is_empty());
+}
@@ -0,0 +1,80 @@
+use serde::{Deserialize, Serialize};
+use serde_automerge::{
+    history::{history, Step},
+    transaction::CommitOptions,
+    AutoCommit, AutomergeSetExtension, Prop, TypedDoc, ROOT,
+};
+
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+struct Counter {
+    value: i64,
+    label: Option<String>,
+}
+
+#[test]
+fn test_snapshot_per_change() {
+    let mut doc = TypedDoc::new(&Counter {
+        value: 0,
+        label: None,
+    })
+    .unwrap();
+    doc.modify(|c| c.value = 1).unwrap();
+    doc.modify(|c| c.label = Some("one".to_owned())).unwrap();
+
+    let snapshots = doc.history().map(|s| s.unwrap().1).collect::<Vec<_>>();
+    assert_eq!(
+        snapshots,
+        vec![
+            Counter {
+                value: 0,
+                label: None
+            },
+            Counter {
+                value: 1,
+                label: None
+            },
+            Counter {
+                value: 1,
+                label: Some("one".to_owned())
+            },
+        ]
+    );
+
+    let values = doc
+        .history()
+        .path::<i64>(vec![Prop::Map("value".to_owned())])
+        .map(|s| s.unwrap().1)
+        .collect::<Vec<i64>>();
+    assert_eq!(values, vec![0, 1, 1]);
+}
+
+#[test]
+fn test_step_by_actor_and_window() {
+    let mut doc = AutoCommit::new();
+    for (time, value) in [(1000, 1), (1500, 2), (2500, 3)] {
+        doc.set_value(ROOT, "value", value).unwrap();
+        doc.commit_with(CommitOptions::default().with_time(time));
+    }
+    let mut other = doc.fork();
+    other.set_value(ROOT, "value", 10).unwrap();
+    other.commit_with(CommitOptions::default().with_time(3000));
+    doc.merge(&mut other).unwrap();
+
+    let actor = other.get_actor().clone();
+    let by_actor = history::<Option<i64>>(doc.document())
+        .step(Step::Actor(actor.clone()))
+        .path::<i64>(vec![Prop::Map("value".to_owned())])
+        .map(|s| s.unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(by_actor.len(), 1);
+    assert_eq!(by_actor[0].0.actor, actor);
+    assert_eq!(by_actor[0].1, 10);
+
+    let windows = history::<Option<i64>>(doc.document())
+        .step(Step::Window(1000))
+        .path::<i64>(vec![Prop::Map("value".to_owned())])
+        .map(|s| s.unwrap())
+        .map(|(meta, value)| (meta.timestamp, value))
+        .collect::<Vec<_>>();
+    assert_eq!(windows, vec![(1500, 2), (2500, 3), (3000, 10)]);
+}
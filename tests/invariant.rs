@@ -0,0 +1,122 @@
+use serde::{Deserialize, Serialize};
+use serde_automerge::{invariant::Invariants, TypedDoc};
+use std::collections::BTreeSet;
+
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+struct Board {
+    ids: Vec<u32>,
+}
+
+fn invariants() -> Invariants<Board> {
+    Invariants::new().with(
+        "unique ids",
+        |b: &Board| {
+            let unique = b.ids.iter().collect::<BTreeSet<_>>();
+            if unique.len() == b.ids.len() {
+                Ok(())
+            } else {
+                Err(format!("{:?} contains duplicates", b.ids))
+            }
+        },
+        |b: &mut Board| {
+            let mut seen = BTreeSet::new();
+            b.ids.retain(|id| seen.insert(*id));
+        },
+    )
+}
+
+#[test]
+fn test_repair_after_merge() {
+    let mut doc = TypedDoc::new(&Board { ids: vec![1] }).unwrap();
+    let mut peer = doc.fork();
+    doc.modify(|b| b.ids.push(2)).unwrap();
+    peer.modify(|b| b.ids.push(2)).unwrap();
+
+    let report = invariants().merge(&mut doc, &mut peer).unwrap();
+    assert_eq!(report.violations.len(), 1);
+    assert_eq!(report.violations[0].invariant, "unique ids");
+    assert!(report.unresolved.is_empty());
+    assert_eq!(doc.read().unwrap().ids, vec![1, 2]);
+
+    // Nothing to repair the second time
+    assert!(invariants().enforce(&mut doc).unwrap().is_clean());
+}
+
+#[test]
+fn test_concurrent_repairs_converge() {
+    let mut a = TypedDoc::new(&Board { ids: vec![1] }).unwrap();
+    let mut b = a.fork();
+    a.modify(|v| v.ids.extend([2, 3])).unwrap();
+    b.modify(|v| v.ids.extend([3, 2])).unwrap();
+
+    // Both peers merge and repair independently
+    let changes_a = a.save();
+    let changes_b = b.save();
+    invariants().load_incremental(&mut a, &changes_b).unwrap();
+    invariants().load_incremental(&mut b, &changes_a).unwrap();
+    assert_eq!(a.read().unwrap(), b.read().unwrap());
+
+    a.merge(&mut b).unwrap();
+    b.merge(&mut a).unwrap();
+    let merged = a.read().unwrap();
+    assert_eq!(merged, b.read().unwrap());
+    assert!(invariants().check(&merged).is_empty());
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+struct Task {
+    id: u32,
+    done: bool,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+struct Tasks {
+    tasks: Vec<Task>,
+}
+
+fn task(id: u32, done: bool) -> Task {
+    Task { id, done }
+}
+
+fn unique_tasks() -> Invariants<Tasks> {
+    Invariants::new().with(
+        "unique ids",
+        |t: &Tasks| {
+            let unique = t.tasks.iter().map(|t| t.id).collect::<BTreeSet<_>>();
+            match unique.len() == t.tasks.len() {
+                true => Ok(()),
+                false => Err("duplicate ids".to_owned()),
+            }
+        },
+        |t: &mut Tasks| {
+            let mut seen = BTreeSet::new();
+            t.tasks.retain(|t| seen.insert(t.id));
+        },
+    )
+}
+
+#[test]
+fn test_repairs_keep_edits_behind_concurrent_inserts() {
+    let mut a = TypedDoc::new(&Tasks {
+        tasks: vec![task(1, false), task(2, false)],
+    })
+    .unwrap();
+    let mut b = a.fork();
+    a.modify(|v| v.tasks.insert(0, task(0, false))).unwrap();
+    b.modify(|v| {
+        v.tasks[1].done = true;
+        v.tasks.push(task(0, false));
+    })
+    .unwrap();
+
+    let changes_a = a.save();
+    let changes_b = b.save();
+    unique_tasks().load_incremental(&mut a, &changes_b).unwrap();
+    unique_tasks().load_incremental(&mut b, &changes_a).unwrap();
+    a.merge(&mut b).unwrap();
+    b.merge(&mut a).unwrap();
+
+    let expected = vec![task(0, false), task(1, false), task(2, true)];
+    assert_eq!(a.read().unwrap().tasks, expected);
+    assert_eq!(b.read().unwrap().tasks, expected);
+}
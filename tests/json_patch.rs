@@ -0,0 +1,226 @@
+#![cfg(feature = "json-patch")]
+
+use serde::{Deserialize, Serialize};
+use serde_automerge::{
+    json_patch::{
+        apply_json_patch, apply_merge_patch, diff_json_patch, to_json_patch, JsonPatchOp,
+    },
+    marks::{ExpandMark, Mark},
+    text::{Block, Span},
+    transaction::Transactable,
+    AutoCommit, AutomergeSerdeError, BlockText, Deserializer, ObjType, ReadDoc, ScalarValue,
+    Serializer, TypedDoc, Value, ROOT,
+};
+use serde_json::json;
+use std::collections::BTreeMap;
+
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
+struct Scene {
+    names: Vec<String>,
+    tags: BTreeMap<String, i64>,
+    title: String,
+}
+
+fn scene() -> Scene {
+    Scene {
+        names: vec!["a".to_owned(), "b".to_owned()],
+        tags: BTreeMap::from([("a/b".to_owned(), 1)]),
+        title: "hi".to_owned(),
+    }
+}
+
+#[test]
+fn test_diff_json_patch() {
+    let mut doc = TypedDoc::new(&scene()).unwrap();
+    let before = doc.get_heads();
+    doc.modify(|s| {
+        s.names.remove(0);
+        s.names.push("c".to_owned());
+        s.tags.insert("a/b".to_owned(), 2);
+        s.tags.insert("x~y".to_owned(), 3);
+    })
+    .unwrap();
+    let after = doc.get_heads();
+
+    let ops = diff_json_patch(doc.doc_mut().document(), &before, &after).unwrap();
+    let ops = serde_json::to_value(ops).unwrap();
+    assert_eq!(
+        ops,
+        json!([
+            { "op": "remove", "path": "/names/0" },
+            { "op": "add", "path": "/names/1", "value": "c" },
+            { "op": "add", "path": "/tags/a~1b", "value": 2 },
+            { "op": "add", "path": "/tags/x~0y", "value": 3 },
+        ])
+    );
+}
+
+#[test]
+fn test_new_objects_and_text() {
+    let mut doc = TypedDoc::new(&scene()).unwrap();
+    let doc = doc.doc_mut();
+    let heads = doc.get_heads();
+    let mut updated = doc.fork();
+    let text = updated.put_object(ROOT, "notes", ObjType::Text).unwrap();
+    updated.splice_text(&text, 0, 0, "hello").unwrap();
+    updated.splice_text(&text, 5, 0, "!").unwrap();
+    updated.put(ROOT, "title", "bye").unwrap();
+    let after = updated.get_heads();
+    let patches = updated.diff(&heads, &after);
+
+    assert_eq!(
+        to_json_patch(&updated, patches).unwrap(),
+        vec![
+            JsonPatchOp::Add {
+                path: "/notes".to_owned(),
+                value: json!(""),
+            },
+            JsonPatchOp::Add {
+                path: "/title".to_owned(),
+                value: json!("bye"),
+            },
+            JsonPatchOp::Replace {
+                path: "/notes".to_owned(),
+                value: json!("hello!"),
+            },
+        ]
+    );
+}
+
+#[test]
+fn test_apply_json_patch() {
+    let mut doc = TypedDoc::new(&scene()).unwrap();
+    let ops: Vec<JsonPatchOp> = serde_json::from_value(json!([
+        { "op": "test", "path": "/title", "value": "hi" },
+        { "op": "add", "path": "/names/-", "value": "c" },
+        { "op": "add", "path": "/names/0", "value": "z" },
+        { "op": "remove", "path": "/names/1" },
+        { "op": "copy", "from": "/tags/a~1b", "path": "/tags/c" },
+        { "op": "move", "from": "/tags/a~1b", "path": "/tags/d" },
+        { "op": "replace", "path": "/title", "value": "bye" },
+    ]))
+    .unwrap();
+    apply_json_patch(doc.doc_mut(), &ops).unwrap();
+    doc.doc_mut().commit();
+
+    assert_eq!(
+        doc.read().unwrap(),
+        Scene {
+            names: vec!["z".to_owned(), "b".to_owned(), "c".to_owned()],
+            tags: BTreeMap::from([("c".to_owned(), 1), ("d".to_owned(), 1)]),
+            title: "bye".to_owned(),
+        }
+    );
+
+    let failing = [JsonPatchOp::Test {
+        path: "/title".to_owned(),
+        value: json!("hi"),
+    }];
+    assert!(matches!(
+        apply_json_patch(doc.doc_mut(), &failing),
+        Err(AutomergeSerdeError::TestFailed(_))
+    ));
+    let missing = [JsonPatchOp::Remove {
+        path: "/names/5".to_owned(),
+    }];
+    assert!(matches!(
+        apply_json_patch(doc.doc_mut(), &missing),
+        Err(AutomergeSerdeError::NotFound(_))
+    ));
+}
+
+#[test]
+fn test_move_keeps_text_and_counters() {
+    let mut doc = AutoCommit::new();
+    let text = doc.put_object(ROOT, "draft", ObjType::Text).unwrap();
+    doc.splice_text(&text, 0, 0, "hello").unwrap();
+    doc.mark(
+        &text,
+        Mark::new("bold".to_owned(), true, 0, 2),
+        ExpandMark::default(),
+    )
+    .unwrap();
+    let list = doc.put_object(ROOT, "counts", ObjType::List).unwrap();
+    doc.insert(&list, 0, ScalarValue::counter(3)).unwrap();
+
+    let ops: Vec<JsonPatchOp> = serde_json::from_value(json!([
+        { "op": "move", "from": "/draft", "path": "/body" },
+        { "op": "move", "from": "/counts/0", "path": "/count" },
+    ]))
+    .unwrap();
+    apply_json_patch(&mut doc, &ops).unwrap();
+
+    assert_eq!(doc.get(ROOT, "draft").unwrap(), None);
+    let (value, text) = doc.get(ROOT, "body").unwrap().unwrap();
+    assert_eq!(value, Value::Object(ObjType::Text));
+    assert_eq!(doc.text(&text).unwrap(), "hello");
+    let marks = doc.marks(&text).unwrap();
+    assert_eq!(marks.len(), 1);
+    assert_eq!(
+        (marks[0].name(), marks[0].start, marks[0].end),
+        ("bold", 0, 2)
+    );
+    assert_eq!(doc.length(&list), 0);
+    assert!(matches!(
+        doc.get(ROOT, "count").unwrap(),
+        Some((Value::Scalar(s), _)) if matches!(*s, ScalarValue::Counter(_))
+    ));
+    doc.increment(ROOT, "count", 1).unwrap();
+}
+
+#[test]
+fn test_copy_keeps_blocks_and_marks() {
+    #[derive(Debug, Deserialize, Serialize, PartialEq)]
+    struct Note {
+        body: BlockText,
+    }
+    let body = BlockText::new(vec![
+        Block::new("heading").with_span(Span::new("Title")),
+        Block::new("paragraph")
+            .with_span(Span::new("some "))
+            .with_span(Span::new("bold").with_mark("bold", true)),
+    ]);
+    let mut doc = AutoCommit::new();
+    Note { body: body.clone() }
+        .serialize(Serializer::new_object(&mut doc, ROOT))
+        .unwrap();
+
+    let ops: Vec<JsonPatchOp> = serde_json::from_value(json!([
+        { "op": "copy", "from": "/body", "path": "/copy" },
+        { "op": "move", "from": "/body", "path": "/moved" },
+    ]))
+    .unwrap();
+    apply_json_patch(&mut doc, &ops).unwrap();
+
+    for key in ["copy", "moved"] {
+        assert_eq!(
+            BlockText::deserialize(Deserializer::new_get(&doc, ROOT, key).unwrap()).unwrap(),
+            body
+        );
+    }
+}
+
+#[test]
+fn test_apply_merge_patch_merges_concurrently() {
+    let mut doc = TypedDoc::new(&scene()).unwrap();
+    let mut other = doc.fork();
+
+    apply_merge_patch(
+        doc.doc_mut(),
+        &json!({ "tags": { "a/b": null, "e": 5 }, "title": "bye" }),
+    )
+    .unwrap();
+    doc.doc_mut().commit();
+    apply_merge_patch(other.doc_mut(), &json!({ "tags": { "f": 6 } })).unwrap();
+    other.doc_mut().commit();
+    doc.merge(&mut other).unwrap();
+
+    assert_eq!(
+        doc.read().unwrap(),
+        Scene {
+            names: vec!["a".to_owned(), "b".to_owned()],
+            tags: BTreeMap::from([("e".to_owned(), 5), ("f".to_owned(), 6)]),
+            title: "bye".to_owned(),
+        }
+    );
+}
@@ -0,0 +1,99 @@
+use serde::{Deserialize, Serialize};
+use serde_automerge::{AutoCommit, ListHandle, ObjId};
+use std::ops::Bound;
+
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+struct Item {
+    name: String,
+    count: u32,
+}
+
+fn item(name: &str, count: u32) -> Item {
+    Item {
+        name: name.to_owned(),
+        count,
+    }
+}
+
+#[test]
+fn test_list_operations() {
+    let mut doc = AutoCommit::new();
+    let list = ListHandle::<Item>::create(&mut doc, ObjId::Root, "items").unwrap();
+    assert!(list.is_empty(&doc));
+
+    list.push(&mut doc, &item("a", 1)).unwrap();
+    list.push(&mut doc, &item("c", 3)).unwrap();
+    list.insert(&mut doc, 1, &item("b", 2)).unwrap();
+    assert_eq!(list.len(&doc), 3);
+    assert_eq!(list.get(&doc, 1).unwrap(), Some(item("b", 2)));
+    assert_eq!(list.get(&doc, 3).unwrap(), None);
+
+    assert_eq!(list.remove(&mut doc, 0).unwrap(), item("a", 1));
+    list.set(&mut doc, 1, &item("c", 4)).unwrap();
+    assert_eq!(list.to_vec(&doc).unwrap(), vec![item("b", 2), item("c", 4)]);
+
+    let removed = list
+        .splice(&mut doc, 0..1, vec![item("x", 0), item("y", 0)])
+        .unwrap();
+    assert_eq!(removed, vec![item("b", 2)]);
+    assert_eq!(
+        list.iter(&doc).collect::<Result<Vec<_>, _>>().unwrap(),
+        vec![item("x", 0), item("y", 0), item("c", 4)]
+    );
+
+    let found = ListHandle::<Item>::new_get(&doc, ObjId::Root, "items")
+        .unwrap()
+        .unwrap();
+    assert_eq!(found.obj(), list.obj());
+}
+
+#[test]
+fn test_concurrent_inserts_merge() {
+    let mut doc = AutoCommit::new();
+    let list = ListHandle::<u32>::create(&mut doc, ObjId::Root, "numbers").unwrap();
+    list.splice(&mut doc, .., [1, 2, 3]).unwrap();
+
+    let mut fork = doc.fork();
+    list.insert(&mut doc, 0, &0).unwrap();
+    list.push(&mut fork, &4).unwrap();
+
+    doc.merge(&mut fork).unwrap();
+    assert_eq!(list.to_vec(&doc).unwrap(), vec![0, 1, 2, 3, 4]);
+}
+
+#[test]
+fn test_splice_out_of_range_leaves_list_untouched() {
+    let mut doc = AutoCommit::new();
+    let list = ListHandle::<u32>::create(&mut doc, ObjId::Root, "numbers").unwrap();
+    for n in 0..3 {
+        list.push(&mut doc, &n).unwrap();
+    }
+
+    assert!(list.splice(&mut doc, 1..5, []).is_err());
+    assert!(list
+        .splice(&mut doc, (Bound::Included(2), Bound::Excluded(1)), [9])
+        .is_err());
+    assert!(list.splice(&mut doc, 0..=usize::MAX, []).is_err());
+    assert_eq!(list.to_vec(&doc).unwrap(), [0, 1, 2]);
+
+    // Splicing at the very end is in range
+    assert!(list.splice(&mut doc, 3.., [3]).unwrap().is_empty());
+    assert_eq!(list.to_vec(&doc).unwrap(), [0, 1, 2, 3]);
+}
+
+#[test]
+fn test_failed_insert_leaves_no_placeholder() {
+    use serde_automerge::ReadDoc;
+    use std::collections::BTreeMap;
+
+    let mut doc = AutoCommit::new();
+    let list = ListHandle::<BTreeMap<(u8, u8), u8>>::create(&mut doc, ObjId::Root, "maps").unwrap();
+    list.push(&mut doc, &BTreeMap::new()).unwrap();
+
+    // Maps with non-string keys can't be serialized
+    let invalid = BTreeMap::from([((1, 2), 3)]);
+    assert!(list.insert(&mut doc, 0, &invalid).is_err());
+    assert!(list.push(&mut doc, &invalid).is_err());
+    assert_eq!(doc.length(list.obj()), 1);
+    assert_eq!(list.to_vec(&doc).unwrap(), [BTreeMap::new()]);
+}
@@ -0,0 +1,66 @@
+use serde::{Deserialize, Serialize};
+use serde_automerge::{AutoCommit, AutomergeGetExtension, AutomergeSetExtension, MapHandle, ObjId};
+use std::collections::{BTreeMap, HashMap};
+
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+struct Player {
+    name: String,
+    score: i64,
+}
+
+fn player(name: &str, score: i64) -> Player {
+    Player {
+        name: name.to_owned(),
+        score,
+    }
+}
+
+#[test]
+fn test_map_operations() {
+    let mut doc = AutoCommit::new();
+    let players = MapHandle::<String, Player>::create(&mut doc, ObjId::Root, "players").unwrap();
+    assert!(players.is_empty(&doc));
+
+    players
+        .insert(&mut doc, &"alice".to_owned(), &player("Alice", 1))
+        .unwrap();
+    players
+        .insert(&mut doc, &"bob".to_owned(), &player("Bob", 2))
+        .unwrap();
+    assert_eq!(players.len(&doc), 2);
+    assert!(players.contains_key(&doc, &"bob".to_owned()).unwrap());
+    assert_eq!(
+        players.get(&doc, &"alice".to_owned()).unwrap(),
+        Some(player("Alice", 1))
+    );
+
+    assert_eq!(
+        players.remove(&mut doc, &"alice".to_owned()).unwrap(),
+        Some(player("Alice", 1))
+    );
+    assert_eq!(players.remove(&mut doc, &"alice".to_owned()).unwrap(), None);
+    assert_eq!(
+        players.keys(&doc).collect::<Result<Vec<_>, _>>().unwrap(),
+        vec!["bob".to_owned()]
+    );
+}
+
+#[test]
+fn test_entries_interchangeable_with_set_value() {
+    let mut doc = AutoCommit::new();
+    let scores = HashMap::from([(1u32, 10i64), (2, 20)]);
+    let id = doc.set_value(ObjId::Root, "scores", &scores).unwrap();
+
+    let handle = MapHandle::<u32, i64>::new(id);
+    assert_eq!(handle.get(&doc, &2).unwrap(), Some(20));
+    handle.insert(&mut doc, &3, &30).unwrap();
+
+    let read = handle
+        .iter(&doc)
+        .collect::<Result<BTreeMap<_, _>, _>>()
+        .unwrap();
+    assert_eq!(read, BTreeMap::from([(1, 10), (2, 20), (3, 30)]));
+
+    let read: HashMap<u32, i64> = doc.get_value(ObjId::Root, "scores").unwrap().unwrap();
+    assert_eq!(read.get(&3), Some(&30));
+}
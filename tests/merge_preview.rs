@@ -0,0 +1,64 @@
+use serde::{Deserialize, Serialize};
+use serde_automerge::{diff::FieldValue, Prop, TypedDoc};
+
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+struct Settings {
+    theme: String,
+    size: i64,
+}
+
+fn settings() -> Settings {
+    Settings {
+        theme: "light".to_owned(),
+        size: 12,
+    }
+}
+
+#[test]
+fn test_preview_leaves_doc_untouched() {
+    let mut doc = TypedDoc::new(&settings()).unwrap();
+    let mut peer = doc.fork();
+    peer.modify(|s| s.size = 14).unwrap();
+    let heads = doc.get_heads();
+
+    let preview = doc.preview_merge(&peer.save()).unwrap();
+    assert_eq!(preview.value.size, 14);
+    assert_eq!(preview.diff.before, settings());
+    assert_eq!(
+        preview
+            .diff
+            .changes
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>(),
+        ["size: 12 → 14"]
+    );
+    assert!(preview.conflicts.is_empty());
+
+    assert_eq!(doc.get_heads(), heads);
+    assert_eq!(doc.read().unwrap(), settings());
+}
+
+#[test]
+fn test_preview_reports_conflicts() {
+    let mut doc = TypedDoc::new(&settings()).unwrap();
+    let mut peer = doc.fork();
+    doc.modify(|s| s.theme = "dark".to_owned()).unwrap();
+    peer.modify(|s| s.theme = "blue".to_owned()).unwrap();
+
+    let preview = doc.preview_merge(&peer.save_incremental()).unwrap();
+    assert_eq!(preview.conflicts.len(), 1);
+    let conflict = &preview.conflicts[0];
+    assert_eq!(conflict.path, vec![Prop::Map("theme".to_owned())]);
+    assert_eq!(conflict.values.len(), 2);
+    assert!(conflict
+        .values
+        .contains(&FieldValue::Str("dark".to_owned())));
+    assert!(conflict
+        .values
+        .contains(&FieldValue::Str("blue".to_owned())));
+    assert_eq!(
+        conflict.values[0],
+        FieldValue::Str(preview.value.theme.clone())
+    );
+}
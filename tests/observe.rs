@@ -0,0 +1,112 @@
+use serde::{Deserialize, Serialize};
+use serde_automerge::{Observers, Prop, TypedDoc};
+use std::{cell::RefCell, rc::Rc};
+
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+struct Float3 {
+    x: i32,
+    y: i32,
+    z: i32,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+struct Player {
+    position: Float3,
+    name: String,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+struct Game {
+    player: Player,
+    score: u32,
+}
+
+fn game() -> Game {
+    Game {
+        player: Player {
+            position: Float3 { x: 1, y: 2, z: 3 },
+            name: "p1".to_owned(),
+        },
+        score: 0,
+    }
+}
+
+fn path(keys: &[&str]) -> Vec<Prop> {
+    keys.iter().map(|k| Prop::Map((*k).to_owned())).collect()
+}
+
+#[test]
+fn test_only_affected_callbacks() {
+    let mut doc = TypedDoc::new(&game()).unwrap();
+    let mut observers = Observers::new().with_heads(doc.get_heads());
+
+    let positions = Rc::new(RefCell::new(Vec::new()));
+    let scores = Rc::new(RefCell::new(Vec::new()));
+    let p = positions.clone();
+    observers.subscribe(path(&["player", "position"]), move |v: Option<Float3>| {
+        p.borrow_mut().push(v)
+    });
+    let s = scores.clone();
+    observers.subscribe(path(&["score"]), move |v: Option<u32>| {
+        s.borrow_mut().push(v)
+    });
+
+    doc.modify(|g| g.player.position.x = 6).unwrap();
+    doc.notify(&mut observers).unwrap();
+    assert_eq!(*positions.borrow(), vec![Some(Float3 { x: 6, y: 2, z: 3 })]);
+    assert!(scores.borrow().is_empty());
+
+    // Nothing changed since the last update
+    doc.notify(&mut observers).unwrap();
+    assert_eq!(positions.borrow().len(), 1);
+}
+
+#[test]
+fn test_merge_and_unsubscribe() {
+    let mut doc = TypedDoc::new(&game()).unwrap();
+    let mut observers = Observers::new().with_heads(doc.get_heads());
+
+    let names = Rc::new(RefCell::new(Vec::new()));
+    let n = names.clone();
+    let id = observers.subscribe(path(&["player"]), move |v: Option<Player>| {
+        n.borrow_mut().push(v.map(|p| p.name))
+    });
+
+    let mut other = doc.fork();
+    other.modify(|g| g.player.name = "p2".to_owned()).unwrap();
+    doc.merge(&mut other).unwrap();
+    doc.notify(&mut observers).unwrap();
+    assert_eq!(*names.borrow(), vec![Some("p2".to_owned())]);
+
+    assert!(observers.unsubscribe(id));
+    assert!(observers.is_empty());
+    doc.modify(|g| g.player.name = "p3".to_owned()).unwrap();
+    doc.notify(&mut observers).unwrap();
+    assert_eq!(names.borrow().len(), 1);
+}
+
+#[test]
+fn test_failing_observer_does_not_block_others() {
+    let mut doc = TypedDoc::new(&game()).unwrap();
+    let mut observers = Observers::new().with_heads(doc.get_heads());
+
+    // The player is a map, so reading it as a string fails
+    let broken = observers.subscribe(path(&["player"]), |_: Option<String>| {
+        panic!("called with a value that can't be read")
+    });
+    let names = Rc::new(RefCell::new(Vec::new()));
+    let n = names.clone();
+    observers.subscribe(path(&["player", "name"]), move |v: Option<String>| {
+        n.borrow_mut().push(v)
+    });
+
+    doc.modify(|g| g.player.name = "p2".to_owned()).unwrap();
+    let errors = doc.notify(&mut observers).unwrap_err();
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].0, broken);
+    assert_eq!(*names.borrow(), vec![Some("p2".to_owned())]);
+
+    // The change was delivered to everyone who could read it and is not replayed
+    doc.notify(&mut observers).unwrap();
+    assert_eq!(names.borrow().len(), 1);
+}
@@ -0,0 +1,128 @@
+use serde::{Deserialize, Serialize};
+use serde_automerge::{
+    diff::FieldValue, repair, transaction::Transactable, AutoCommit, Deserializer, ObjId, ObjType,
+    Prop, ReadDoc, ScalarValue,
+};
+
+#[derive(Debug, Default, Deserialize, Serialize, PartialEq)]
+struct Position {
+    x: i64,
+    y: i64,
+}
+
+#[derive(Debug, Deserialize, Serialize, PartialEq)]
+struct Item {
+    name: String,
+    count: u32,
+    position: Position,
+    tags: Vec<String>,
+    done: bool,
+}
+
+#[test]
+fn test_repair_coerces_values() {
+    let mut doc = AutoCommit::new();
+    doc.put(ObjId::Root, "name", 7).unwrap();
+    doc.put(ObjId::Root, "count", "42").unwrap();
+    doc.put(ObjId::Root, "position", "here").unwrap();
+    let tags = doc.put_object(ObjId::Root, "tags", ObjType::List).unwrap();
+    doc.insert(&tags, 0, "a").unwrap();
+    doc.insert(&tags, 1, true).unwrap();
+    doc.put(ObjId::Root, "legacy", 1).unwrap();
+
+    let repaired = repair::<Item, _>(&mut doc, ObjId::Root).unwrap();
+    let expected = Item {
+        name: "7".into(),
+        count: 42,
+        position: Position::default(),
+        tags: vec!["a".into(), "true".into()],
+        done: false,
+    };
+    assert_eq!(repaired.value, expected);
+
+    let changes = repaired
+        .changes
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>();
+    assert_eq!(
+        changes,
+        [
+            r#"count: "42" → 42"#,
+            "done: - → false",
+            "legacy: 1 → -",
+            r#"name: 7 → "7""#,
+            r#"position: "here" → {x: 0, y: 0}"#,
+            r#"tags[1]: true → "true""#,
+        ]
+    );
+    assert_eq!(
+        repaired.changes[1].path,
+        [Prop::Map("done".into())].to_vec()
+    );
+
+    // The document now reads strictly, and there is nothing left to repair
+    assert_eq!(
+        Item::deserialize(Deserializer::new_root(&doc)).unwrap(),
+        expected
+    );
+    assert!(repair::<Item, _>(&mut doc, ObjId::Root).unwrap().is_clean());
+}
+
+#[test]
+fn test_repair_nested_object() {
+    let mut doc = AutoCommit::new();
+    let position = doc
+        .put_object(ObjId::Root, "position", ObjType::Map)
+        .unwrap();
+    doc.put(&position, "x", 1.0).unwrap();
+    doc.put(&position, "y", ScalarValue::Null).unwrap();
+
+    let repaired = repair::<Position, _>(&mut doc, position.clone()).unwrap();
+    assert_eq!(repaired.value, Position { x: 1, y: 0 });
+    assert_eq!(repaired.changes.len(), 2);
+    assert_eq!(repaired.changes[1].old, Some(FieldValue::Null));
+    assert_eq!(
+        doc.get(&position, "x").unwrap().unwrap().0.to_i64(),
+        Some(1)
+    );
+}
+
+#[derive(Debug, Deserialize, Serialize, PartialEq)]
+struct Note {
+    title: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    due: Option<String>,
+    priority: Option<i64>,
+}
+
+#[test]
+fn test_repair_accepts_missing_options() {
+    let mut doc = AutoCommit::new();
+    doc.put(ObjId::Root, "title", "a").unwrap();
+
+    let repaired = repair::<Note, _>(&mut doc, ObjId::Root).unwrap();
+    assert!(repaired.is_clean());
+    assert_eq!(
+        repaired.value,
+        Note {
+            title: "a".into(),
+            due: None,
+            priority: None,
+        }
+    );
+    assert!(doc.get(ObjId::Root, "due").unwrap().is_none());
+
+    // An optional value that is present still has to fit the type
+    doc.put(ObjId::Root, "due", 3).unwrap();
+    let repaired = repair::<Note, _>(&mut doc, ObjId::Root).unwrap();
+    assert_eq!(repaired.value.due.as_deref(), Some("3"));
+    assert_eq!(
+        repaired
+            .changes
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>(),
+        [r#"due: 3 → "3""#]
+    );
+}
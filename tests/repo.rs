@@ -0,0 +1,252 @@
+#![cfg(feature = "repo")]
+
+use serde::{Deserialize, Serialize};
+use serde_automerge::{
+    repo::{DocumentId, PeerId, RepoMessage, RepoPeer, StorageKey},
+    storage::{DocStore, FsStorage, MemoryStorage, Storage},
+    AutomergeSerdeError, TypedDoc,
+};
+use std::{collections::VecDeque, path::PathBuf};
+
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+struct Todo {
+    title: String,
+    done: bool,
+}
+
+/// Deliver encoded messages between two peers until neither has anything left to send.
+fn exchange(a: &mut RepoPeer, b: &mut RepoPeer, messages: Vec<RepoMessage>) {
+    let mut queue = messages.into_iter().collect::<VecDeque<_>>();
+    while let Some(message) = queue.pop_front() {
+        let bytes = message.encode().unwrap();
+        let message = RepoMessage::decode(&bytes).unwrap().unwrap();
+        let target = match &message {
+            RepoMessage::Join { sender_id, .. } if sender_id == a.peer_id() => &mut *b,
+            RepoMessage::Join { .. } => &mut *a,
+            RepoMessage::Peer { target_id, .. }
+            | RepoMessage::Sync { target_id, .. }
+            | RepoMessage::Request { target_id, .. }
+            | RepoMessage::DocUnavailable { target_id, .. } => {
+                if target_id == a.peer_id() {
+                    &mut *a
+                } else {
+                    &mut *b
+                }
+            }
+            RepoMessage::Error { message } => panic!("{message}"),
+        };
+        queue.extend(target.receive(message).unwrap());
+    }
+}
+
+#[test]
+fn test_request_and_sync_between_peers() {
+    let mut server = RepoPeer::new("server".into());
+    let mut client = RepoPeer::new("client".into());
+    let join = client.join();
+    exchange(&mut client, &mut server, vec![join]);
+    assert_eq!(
+        server.peers().collect::<Vec<_>>(),
+        [&PeerId::from("client")]
+    );
+    assert_eq!(
+        client.peers().collect::<Vec<_>>(),
+        [&PeerId::from("server")]
+    );
+
+    let todo = Todo {
+        title: "write tests".to_owned(),
+        done: false,
+    };
+    let id = server.create(&todo).unwrap();
+    let request = client.request(id, "server".into()).unwrap();
+    exchange(&mut client, &mut server, vec![request]);
+    assert_eq!(client.read::<Todo>(&id).unwrap(), Some(todo.clone()));
+
+    let done = Todo { done: true, ..todo };
+    let messages = client.write(&id, &done).unwrap();
+    exchange(&mut client, &mut server, messages);
+    assert_eq!(server.read::<Todo>(&id).unwrap(), Some(done));
+
+    let missing = DocumentId::new();
+    let request = client.request(missing, "server".into()).unwrap();
+    let response = server.receive(request).unwrap();
+    assert!(matches!(
+        response.as_slice(),
+        [RepoMessage::DocUnavailable { document_id, .. }] if *document_id == missing
+    ));
+}
+
+#[test]
+fn test_requested_doc_is_not_shared_before_it_arrives() {
+    let mut server = RepoPeer::new("server".into());
+    let mut client = RepoPeer::new("client".into());
+    let mut other = RepoPeer::new("other".into());
+    let id = DocumentId::new();
+
+    let request = client.request(id, "server".into()).unwrap();
+    // Another peer asks the client for the document while its request is still open
+    let request_other = other.request(id, "client".into()).unwrap();
+    assert!(matches!(
+        client.receive(request_other).unwrap().as_slice(),
+        [RepoMessage::DocUnavailable { .. }]
+    ));
+    assert_eq!(client.read::<Todo>(&id).unwrap(), None);
+
+    exchange(&mut client, &mut server, vec![request]);
+    assert!(client.document(&id).is_none());
+    assert_eq!(client.read::<Todo>(&id).unwrap(), None);
+}
+
+#[test]
+fn test_unknown_message_types_are_skipped() {
+    let mut bytes = Vec::new();
+    let ephemeral = ciborium::Value::Map(vec![
+        (
+            ciborium::Value::Text("type".to_owned()),
+            ciborium::Value::Text("ephemeral".to_owned()),
+        ),
+        (
+            ciborium::Value::Text("data".to_owned()),
+            ciborium::Value::Bytes(vec![1]),
+        ),
+    ]);
+    ciborium::into_writer(&ephemeral, &mut bytes).unwrap();
+    assert_eq!(RepoMessage::decode(&bytes).unwrap(), None);
+
+    let error = RepoMessage::decode(&[0xff]).unwrap_err();
+    assert!(matches!(error, AutomergeSerdeError::Decode(_)));
+    assert!(std::error::Error::source(&error).is_some());
+}
+
+#[test]
+fn test_wire_format_and_storage_keys() {
+    let id = DocumentId::from_bytes([7; 16]);
+    assert_eq!(id.to_string().parse::<DocumentId>().unwrap(), id);
+    assert_eq!(DocumentId::from_url(&id.to_url()).unwrap(), id);
+    assert!(id.to_url().starts_with("automerge:"));
+    assert!("not-an-id".parse::<DocumentId>().is_err());
+
+    let message = RepoMessage::Sync {
+        sender_id: "a".into(),
+        target_id: "b".into(),
+        document_id: id,
+        data: vec![1, 2, 3],
+    };
+    let value: ciborium::Value = ciborium::from_reader(&message.encode().unwrap()[..]).unwrap();
+    let map = value.into_map().unwrap();
+    let get = |key: &str| {
+        map.iter()
+            .find(|(k, _)| k.as_text() == Some(key))
+            .map(|(_, v)| v.clone())
+    };
+    assert_eq!(get("type"), Some(ciborium::Value::Text("sync".to_owned())));
+    assert_eq!(
+        get("documentId"),
+        Some(ciborium::Value::Text(id.to_string()))
+    );
+    assert_eq!(get("data"), Some(ciborium::Value::Bytes(vec![1, 2, 3])));
+
+    let key = StorageKey::incremental(id, b"chunk");
+    let parts = key.to_parts();
+    assert_eq!(parts[1], "incremental");
+    assert_eq!(parts[2].len(), 64);
+    assert_eq!(StorageKey::from_parts(&parts), Some(key.clone()));
+    let id = id.to_string();
+    let expected: PathBuf = [&id[..2], &id[2..], "incremental", &parts[2]]
+        .iter()
+        .collect();
+    assert_eq!(key.to_path(), expected);
+}
+
+#[test]
+fn test_doc_store_uses_repo_storage_keys() {
+    let id = DocumentId::new();
+    let mut store = DocStore::new(MemoryStorage::new()).with_compact_after(1);
+    let mut doc = TypedDoc::new(&Todo {
+        title: "a".to_owned(),
+        done: false,
+    })
+    .unwrap();
+    store.save(&id.to_string(), &mut doc).unwrap();
+    let key = store.storage().keys().next().unwrap().to_owned();
+    let chunk = store.storage().get(&key).unwrap().unwrap();
+    assert_eq!(
+        PathBuf::from(&key),
+        StorageKey::incremental(id, &chunk).to_path()
+    );
+
+    doc.modify(|t| t.done = true).unwrap();
+    store.save(&id.to_string(), &mut doc).unwrap();
+    let keys = store
+        .storage()
+        .keys()
+        .map(PathBuf::from)
+        .collect::<Vec<_>>();
+    assert_eq!(keys, [StorageKey::snapshot(id, &doc.get_heads()).to_path()]);
+}
+
+#[test]
+fn test_doc_store_files_are_where_automerge_repo_looks() {
+    let root = std::env::temp_dir().join(format!("serde-automerge-repo-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&root);
+    let id = DocumentId::from_bytes([7; 16]);
+    let name = id.to_string();
+    let mut store = DocStore::new(FsStorage::new(&root)).with_compact_after(1);
+    let mut doc = TypedDoc::new(&Todo {
+        title: "a".to_owned(),
+        done: false,
+    })
+    .unwrap();
+    store.save(&name, &mut doc).unwrap();
+    doc.modify(|t| t.done = true).unwrap();
+    store.save(&name, &mut doc).unwrap();
+
+    let dir = root.join(&name[..2]).join(&name[2..]).join("snapshot");
+    let files = std::fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+        .collect::<Vec<_>>();
+    let hash = StorageKey::snapshot(id, &doc.get_heads()).to_parts()[2].clone();
+    assert_eq!(files, [hash]);
+    std::fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn test_write_keeps_text_backed_strings() {
+    use serde_automerge::{transaction::Transactable, AutoCommit, ObjType, ReadDoc, Value, ROOT};
+
+    // Strings are text objects in documents created by automerge-repo in JavaScript
+    let mut base = AutoCommit::new();
+    let title = base.put_object(ROOT, "title", ObjType::Text).unwrap();
+    base.splice_text(&title, 0, 0, "write tests").unwrap();
+    base.put(ROOT, "done", false).unwrap();
+    let mut edited = base.fork();
+    edited.splice_text(&title, 11, 0, " first").unwrap();
+
+    let id = DocumentId::new();
+    let mut server = RepoPeer::new("server".into());
+    let mut client = RepoPeer::new("client".into());
+    server.insert_document(id, edited);
+    client.insert_document(id, base);
+
+    let done = Todo {
+        title: "write tests".to_owned(),
+        done: true,
+    };
+    client.write(&id, &done).unwrap();
+    let request = client.request(id, "server".into()).unwrap();
+    exchange(&mut client, &mut server, vec![request]);
+
+    let expected = Todo {
+        title: "write tests first".to_owned(),
+        done: true,
+    };
+    assert_eq!(client.read::<Todo>(&id).unwrap(), Some(expected.clone()));
+    assert_eq!(server.read::<Todo>(&id).unwrap(), Some(expected));
+    let doc = client.document(&id).unwrap();
+    assert_eq!(
+        doc.get(ROOT, "title").unwrap().unwrap(),
+        (Value::Object(ObjType::Text), title)
+    );
+}
@@ -0,0 +1,104 @@
+use serde::{Deserialize, Serialize};
+use serde_automerge::{
+    text::Span, transaction::Transactable, AutoCommit, AutomergeGetExtension,
+    AutomergeReconcileExtension, AutomergeSetExtension, ObjId, ObjType, ReadDoc, RichText,
+    TypedDoc, Value,
+};
+
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
+struct Note {
+    title: String,
+    body: RichText,
+}
+
+fn body() -> RichText {
+    RichText::new(vec![
+        Span::new("Hello "),
+        Span::new("bold").with_mark("bold", true),
+        Span::new(" and a "),
+        Span::new("link").with_mark("link", "https://example.com"),
+    ])
+}
+
+#[test]
+fn test_rich_text_roundtrip() {
+    let note = Note {
+        title: "Note".to_owned(),
+        body: body(),
+    };
+    let doc = TypedDoc::new(&note).unwrap();
+    assert!(matches!(
+        doc.doc().get(ObjId::Root, "body").unwrap(),
+        Some((Value::Object(ObjType::Text), _))
+    ));
+    assert_eq!(doc.read().unwrap(), note);
+    assert_eq!(
+        doc.doc()
+            .text(doc.doc().get(ObjId::Root, "body").unwrap().unwrap().1)
+            .unwrap(),
+        "Hello bold and a link"
+    );
+}
+
+#[test]
+fn test_concurrent_marks_and_edits_merge() {
+    let mut doc = TypedDoc::new(&Note {
+        title: "Note".to_owned(),
+        body: body(),
+    })
+    .unwrap();
+    let mut fork = doc.fork();
+
+    // Remove the bold mark and make "Hello" italic
+    doc.modify(|n| {
+        n.body.spans[0] = Span::new("Hello").with_mark("italic", true);
+        n.body.spans[1] = Span::new(" bold");
+        n.body.normalize();
+    })
+    .unwrap();
+    // Concurrently append some text
+    fork.modify(|n| n.body.spans.push(Span::new("!"))).unwrap();
+
+    doc.merge(&mut fork).unwrap();
+    let mut expected = RichText::new(vec![
+        Span::new("Hello").with_mark("italic", true),
+        Span::new(" bold and a "),
+        Span::new("link").with_mark("link", "https://example.com"),
+        Span::new("!"),
+    ]);
+    expected.normalize();
+    assert_eq!(doc.read().unwrap().body, expected);
+}
+
+#[test]
+fn test_plain_string_reads_as_rich_text() {
+    let mut doc = AutoCommit::new();
+    doc.set_value(ObjId::Root, "body", "plain").unwrap();
+    let body: RichText = doc.get_value(ObjId::Root, "body").unwrap().unwrap();
+    assert_eq!(body, RichText::from("plain"));
+}
+
+#[test]
+fn test_restyle_only_writes_changed_ranges() {
+    let mut doc = AutoCommit::new();
+    let bold = RichText::new(vec![Span::new("abcdef").with_mark("bold", true)]);
+    doc.reconcile_value(ObjId::Root, "body", &bold).unwrap();
+    doc.commit();
+
+    // Rewriting the same text and marks is a no-op
+    doc.reconcile_value(ObjId::Root, "body", &bold).unwrap();
+    assert_eq!(doc.pending_ops(), 0);
+
+    let restyled = RichText::new(vec![
+        Span::new("ab").with_mark("bold", true),
+        Span::new("cd"),
+        Span::new("ef")
+            .with_mark("bold", true)
+            .with_mark("link", "x"),
+    ]);
+    doc.reconcile_value(ObjId::Root, "body", &restyled).unwrap();
+    // One unmark of "cd" and one new link mark on "ef", each a begin and an end op
+    assert_eq!(doc.pending_ops(), 4);
+    let read: Option<RichText> = doc.get_value(ObjId::Root, "body").unwrap();
+    assert_eq!(read, Some(restyled));
+}
@@ -0,0 +1,105 @@
+use serde::{Deserialize, Serialize};
+use serde_automerge::{transaction::Transactable, Prop, SharedDoc, ROOT};
+use std::thread;
+
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+struct Stats {
+    hits: i64,
+    name: String,
+}
+
+fn stats() -> Stats {
+    Stats {
+        hits: 0,
+        name: "a".to_owned(),
+    }
+}
+
+#[test]
+fn test_modify_from_threads() {
+    let doc = SharedDoc::new(&stats()).unwrap();
+    let changes = doc.subscribe();
+
+    let threads = (0..4)
+        .map(|_| {
+            let doc = doc.clone();
+            thread::spawn(move || {
+                for _ in 0..10 {
+                    doc.modify(|s| s.hits += 1).unwrap();
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+
+    assert_eq!(doc.read().unwrap().hits, 40);
+    let changes = changes.try_iter().collect::<Vec<_>>();
+    assert_eq!(changes.len(), 40);
+    assert!(changes
+        .iter()
+        .all(|c| c == &vec![vec![Prop::Map("hits".to_owned())]]));
+}
+
+#[test]
+fn test_merge_from_bytes_notifies() {
+    let doc = SharedDoc::new(&stats()).unwrap();
+    let other =
+        SharedDoc::<Stats>::from_doc(serde_automerge::AutoCommit::load(&doc.save()).unwrap());
+    other.modify(|s| s.name = "b".to_owned()).unwrap();
+
+    let changes = doc.subscribe();
+    // Nothing changes when writing an equal value
+    doc.write(&stats()).unwrap();
+    doc.merge_from_bytes(&other.save()).unwrap();
+    assert_eq!(doc.read().unwrap().name, "b");
+    assert_eq!(
+        changes.try_iter().collect::<Vec<_>>(),
+        vec![vec![vec![Prop::Map("name".to_owned())]]]
+    );
+
+    drop(changes);
+    doc.modify(|s| s.hits = 1).unwrap();
+    assert_eq!(doc.get_heads().len(), 1);
+}
+
+#[test]
+fn test_pending_ops_of_a_panicked_thread_are_committed() {
+    let doc = SharedDoc::new(&stats()).unwrap();
+    let changes = doc.subscribe();
+    let panicking = doc.clone();
+    thread::spawn(move || {
+        panicking.with_doc_mut(|d| {
+            d.put(ROOT, "name", "b").unwrap();
+            panic!("left uncommitted");
+        })
+    })
+    .join()
+    .unwrap_err();
+
+    doc.modify(|s| s.hits = 1).unwrap();
+    assert_eq!(
+        doc.read().unwrap(),
+        Stats {
+            hits: 1,
+            name: "b".to_owned(),
+        }
+    );
+    let mut changed = changes.try_iter().flatten().collect::<Vec<_>>();
+    changed.sort();
+    assert_eq!(
+        changed,
+        vec![
+            vec![Prop::Map("hits".to_owned())],
+            vec![Prop::Map("name".to_owned())]
+        ]
+    );
+    let heads = doc.get_heads();
+    assert_eq!(
+        heads,
+        serde_automerge::AutoCommit::load(&doc.save())
+            .unwrap()
+            .get_heads()
+    );
+}
@@ -0,0 +1,128 @@
+use serde::{Deserialize, Serialize};
+use serde_automerge::{
+    storage::{from_bytes, DocStore, FsStorage, MemoryStorage, Storage},
+    AutomergeSerdeError, TypedDoc,
+};
+use std::io::ErrorKind;
+
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+struct Counter {
+    value: i64,
+    log: Vec<String>,
+}
+
+fn counter() -> Counter {
+    Counter {
+        value: 0,
+        log: vec![],
+    }
+}
+
+#[test]
+fn test_incremental_saves_and_compaction() {
+    let mut store = DocStore::new(MemoryStorage::new()).with_compact_after(3);
+    assert!(store.load::<Counter>("doc").unwrap().is_none());
+
+    let mut doc = TypedDoc::new(&counter()).unwrap();
+    store.save("doc", &mut doc).unwrap();
+    for i in 1..=2 {
+        doc.modify(|c| c.value = i).unwrap();
+        store.save("doc", &mut doc).unwrap();
+    }
+    // Saving without changes does not write a chunk
+    store.save("doc", &mut doc).unwrap();
+    let keys = store.storage().keys().collect::<Vec<_>>();
+    assert_eq!(keys.len(), 3);
+    assert!(keys.iter().all(|key| key.starts_with("do/c/incremental/")));
+    assert_eq!(
+        store.load_value::<Counter>("doc").unwrap().unwrap().value,
+        2
+    );
+
+    doc.modify(|c| c.log.push("compacted".to_owned())).unwrap();
+    store.save("doc", &mut doc).unwrap();
+    let keys = store.storage().keys().collect::<Vec<_>>();
+    assert!(matches!(keys.as_slice(), [key] if key.starts_with("do/c/snapshot/")));
+
+    let snapshot = store.storage().clone();
+    let mut loaded = DocStore::new(snapshot)
+        .load::<Counter>("doc")
+        .unwrap()
+        .unwrap();
+    assert_eq!(loaded.read().unwrap(), doc.read().unwrap());
+    assert_eq!(
+        from_bytes::<Counter>(&loaded.save()).unwrap(),
+        doc.read().unwrap()
+    );
+}
+
+#[test]
+fn test_fs_storage_roundtrip() {
+    let root = std::env::temp_dir().join(format!("serde-automerge-storage-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&root);
+
+    let mut store = DocStore::new(FsStorage::new(&root));
+    let mut doc = TypedDoc::new(&counter()).unwrap();
+    store.save("a/b", &mut doc).unwrap();
+    doc.modify(|c| c.value = 5).unwrap();
+    store.save("a/b", &mut doc).unwrap();
+
+    // Reopen from disk and keep saving on top of what was loaded
+    let store = DocStore::new(FsStorage::new(&root));
+    let mut loaded = store.load::<Counter>("a/b").unwrap().unwrap();
+    assert_eq!(loaded.read().unwrap().value, 5);
+    let mut store = store.with_compact_after(1);
+    loaded.modify(|c| c.value = 6).unwrap();
+    store.save("a/b", &mut loaded).unwrap();
+
+    assert_eq!(
+        std::fs::read_dir(root.join("a/b/snapshot"))
+            .unwrap()
+            .count(),
+        1
+    );
+    assert_eq!(
+        std::fs::read_dir(root.join("a/b/incremental"))
+            .unwrap()
+            .count(),
+        0
+    );
+    assert_eq!(
+        store.load_value::<Counter>("a/b").unwrap().unwrap().value,
+        6
+    );
+    std::fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn test_fs_storage_rejects_keys_outside_root() {
+    let root = std::env::temp_dir().join(format!("serde-automerge-keys-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&root);
+    let mut storage = FsStorage::new(root.join("store"));
+
+    for key in [
+        "../escape",
+        "a/../../escape",
+        "/etc/passwd",
+        "a//b",
+        "./a",
+        "a/",
+    ] {
+        let error = storage.put(key, b"x").unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::InvalidInput, "{key}");
+        assert_eq!(
+            storage.get(key).unwrap_err().kind(),
+            ErrorKind::InvalidInput
+        );
+    }
+    assert!(!root.join("escape").exists());
+
+    let mut store = DocStore::new(storage);
+    let mut doc = TypedDoc::new(&counter()).unwrap();
+    assert!(matches!(
+        store.save("..", &mut doc),
+        Err(AutomergeSerdeError::Io(e)) if e.kind() == ErrorKind::InvalidInput
+    ));
+    // Nothing was written, not even the root
+    assert!(!root.exists());
+}
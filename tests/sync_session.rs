@@ -0,0 +1,79 @@
+use serde::{Deserialize, Serialize};
+use serde_automerge::{session::is_changed, sync::Message, Prop, SyncSession, TypedDoc};
+
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+struct Float3 {
+    x: i32,
+    y: i32,
+    z: i32,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+struct Scene {
+    camera: Float3,
+    numbers: Vec<i32>,
+}
+
+fn scene() -> Scene {
+    Scene {
+        camera: Float3 { x: 1, y: 2, z: 3 },
+        numbers: vec![1, 2],
+    }
+}
+
+/// Exchange encoded messages until both sides are in sync, returning the paths each side saw
+/// change.
+fn sync(
+    a: &mut SyncSession<Scene>,
+    b: &mut SyncSession<Scene>,
+) -> (Vec<Vec<Prop>>, Vec<Vec<Prop>>) {
+    let (mut changed_a, mut changed_b) = (Vec::new(), Vec::new());
+    loop {
+        let to_b = a.generate_message().map(Message::encode);
+        let to_a = b.generate_message().map(Message::encode);
+        if to_a.is_none() && to_b.is_none() {
+            return (changed_a, changed_b);
+        }
+        if let Some(message) = to_b {
+            let message = Message::decode(&message).unwrap();
+            changed_b.extend(b.receive_message(message).unwrap());
+        }
+        if let Some(message) = to_a {
+            let message = Message::decode(&message).unwrap();
+            changed_a.extend(a.receive_message(message).unwrap());
+        }
+    }
+}
+
+#[test]
+fn test_sync_from_empty() {
+    let mut a = SyncSession::new(TypedDoc::new(&scene()).unwrap());
+    let mut b = SyncSession::new(TypedDoc::from_doc(Default::default()));
+
+    let (changed_a, changed_b) = sync(&mut a, &mut b);
+    assert!(changed_a.is_empty());
+    assert!(is_changed(&changed_b, &[Prop::Map("camera".to_owned())]));
+    assert_eq!(b.read().unwrap(), scene());
+}
+
+#[test]
+fn test_sync_reports_changed_paths() {
+    let mut a = SyncSession::new(TypedDoc::new(&scene()).unwrap());
+    let mut b = SyncSession::new(a.doc_mut().fork());
+    sync(&mut a, &mut b);
+
+    a.doc_mut().modify(|s| s.camera.x = 7).unwrap();
+    b.doc_mut().modify(|s| s.numbers.push(3)).unwrap();
+    let (changed_a, changed_b) = sync(&mut a, &mut b);
+
+    assert_eq!(changed_a, vec![vec![Prop::Map("numbers".to_owned())]]);
+    assert_eq!(
+        changed_b,
+        vec![vec![
+            Prop::Map("camera".to_owned()),
+            Prop::Map("x".to_owned())
+        ]]
+    );
+    assert_eq!(a.read().unwrap(), b.read().unwrap());
+    assert_eq!(a.read().unwrap().numbers, vec![1, 2, 3]);
+}
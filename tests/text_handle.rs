@@ -0,0 +1,90 @@
+use serde_automerge::{handle::convert_index, AutoCommit, ObjId, TextEncoding, TextHandle};
+
+#[test]
+fn test_text_editing_and_cursors() {
+    let mut doc = AutoCommit::new();
+    let text = TextHandle::create(&mut doc, ObjId::Root, "note").unwrap();
+    text.insert_str(&mut doc, 0, "hello world").unwrap();
+    let cursor = text.cursor(&doc, 6).unwrap();
+
+    let mut fork = doc.fork();
+    text.splice(&mut doc, 0, 5, "goodbye").unwrap();
+    text.insert_str(&mut fork, 6, "big ").unwrap();
+    text.delete_range(&mut fork, 11..15).unwrap();
+
+    doc.merge(&mut fork).unwrap();
+    assert_eq!(text.text(&doc).unwrap(), "goodbye big w");
+    // The cursor still points at the "w" after both edits
+    assert_eq!(text.cursor_position(&doc, &cursor).unwrap(), 12);
+
+    text.update(&mut doc, "goodbye, world").unwrap();
+    let found = TextHandle::new_get(&doc, ObjId::Root, "note")
+        .unwrap()
+        .unwrap();
+    assert_eq!(found.text(&doc).unwrap(), "goodbye, world");
+}
+
+#[test]
+fn test_index_conversion() {
+    let s = "a😀é";
+    assert_eq!(
+        convert_index(
+            s,
+            2,
+            TextEncoding::UnicodeCodePoint,
+            TextEncoding::Utf16CodeUnit
+        ),
+        Some(3)
+    );
+    assert_eq!(
+        convert_index(
+            s,
+            3,
+            TextEncoding::Utf16CodeUnit,
+            TextEncoding::Utf8CodeUnit
+        ),
+        Some(5)
+    );
+    assert_eq!(
+        convert_index(
+            s,
+            7,
+            TextEncoding::Utf8CodeUnit,
+            TextEncoding::UnicodeCodePoint
+        ),
+        Some(3)
+    );
+    // In the middle of a surrogate pair
+    assert_eq!(
+        convert_index(
+            s,
+            2,
+            TextEncoding::Utf16CodeUnit,
+            TextEncoding::UnicodeCodePoint
+        ),
+        None
+    );
+    assert_eq!(
+        convert_index(
+            s,
+            4,
+            TextEncoding::UnicodeCodePoint,
+            TextEncoding::Utf8CodeUnit
+        ),
+        None
+    );
+
+    let mut doc = AutoCommit::new();
+    let text = TextHandle::create(&mut doc, ObjId::Root, "note").unwrap();
+    text.insert_str(&mut doc, 0, s).unwrap();
+    let native = text
+        .from_encoding(&doc, 3, TextEncoding::Utf16CodeUnit)
+        .unwrap();
+    text.insert_str(&mut doc, native, "!").unwrap();
+    assert_eq!(text.text(&doc).unwrap(), "a😀!é");
+    assert_eq!(
+        text.to_encoding(&doc, native + 1, TextEncoding::Utf16CodeUnit)
+            .unwrap(),
+        4
+    );
+}
@@ -0,0 +1,151 @@
+#![cfg(feature = "tokio")]
+
+use serde::{Deserialize, Serialize};
+use serde_automerge::{
+    transaction::Transactable,
+    transport::{connect, reconnect, Backoff, MAX_FRAME_LEN},
+    AutomergeSerdeError, ObjType, SyncSession, TypedDoc, ROOT,
+};
+use std::{future::ready, io, time::Duration};
+use tokio::{io::AsyncWriteExt, sync::mpsc};
+
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+struct Scene {
+    title: String,
+    numbers: Vec<i32>,
+}
+
+fn scene() -> Scene {
+    Scene {
+        title: "a".to_owned(),
+        numbers: vec![1],
+    }
+}
+
+#[tokio::test]
+async fn test_sync_over_duplex() {
+    let (io_a, io_b) = tokio::io::duplex(1024);
+    let mut doc_a = TypedDoc::new(&scene()).unwrap();
+    let doc_b = doc_a.fork();
+    let (a, run_a) = connect(SyncSession::new(doc_a), io_a);
+    let (mut b, run_b) = connect(SyncSession::new(doc_b), io_b);
+    let run_a = tokio::spawn(run_a);
+    let run_b = tokio::spawn(run_b);
+
+    a.modify(|s| s.numbers.push(2)).await.unwrap();
+    let update = b.next_update().await.unwrap().unwrap();
+    assert_eq!(update.numbers, vec![1, 2]);
+    assert_eq!(b.read().await.unwrap(), a.read().await.unwrap());
+
+    // Dropping a side closes its stream, which ends the loop of the other side
+    run_a.abort();
+    let session = run_b.await.unwrap().unwrap();
+    assert_eq!(session.read().unwrap().numbers, vec![1, 2]);
+    assert!(a.read().await.is_err());
+}
+
+#[tokio::test]
+async fn test_sync_into_empty_doc() {
+    let (io_a, io_b) = tokio::io::duplex(64);
+    let (_a, run_a) = connect(SyncSession::new(TypedDoc::new(&scene()).unwrap()), io_a);
+    let (mut b, run_b) = connect(
+        SyncSession::<Scene>::new(TypedDoc::from_doc(Default::default())),
+        io_b,
+    );
+    tokio::spawn(run_a);
+    tokio::spawn(run_b);
+
+    assert_eq!(b.next_update().await.unwrap().unwrap(), scene());
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+struct Untitled {
+    title: i64,
+}
+
+#[tokio::test]
+async fn test_invalid_update_does_not_stop_syncing() {
+    let (io_a, io_b) = tokio::io::duplex(1024);
+    let (a, run_a) = connect(
+        SyncSession::new(TypedDoc::new(&Untitled { title: 1 }).unwrap()),
+        io_a,
+    );
+    let (mut b, run_b) = connect(
+        SyncSession::<Scene>::new(TypedDoc::from_doc(Default::default())),
+        io_b,
+    );
+    tokio::spawn(run_a);
+    tokio::spawn(run_b);
+
+    assert!(b.next_update().await.unwrap().is_err());
+    a.with_doc(|doc| {
+        let doc = doc.doc_mut();
+        doc.put(ROOT, "title", "a").unwrap();
+        let numbers = doc.put_object(ROOT, "numbers", ObjType::List).unwrap();
+        doc.insert(&numbers, 0, 1).unwrap();
+    })
+    .await
+    .unwrap();
+    assert_eq!(b.next_update().await.unwrap().unwrap(), scene());
+}
+
+#[tokio::test]
+async fn test_oversized_frame_is_rejected() {
+    let (mut io_a, io_b) = tokio::io::duplex(64);
+    let (_b, run_b) = connect(SyncSession::new(TypedDoc::new(&scene()).unwrap()), io_b);
+    let run_b = tokio::spawn(run_b);
+
+    io_a.write_u32(MAX_FRAME_LEN as u32 + 1).await.unwrap();
+    assert!(matches!(
+        run_b.await.unwrap(),
+        Err(AutomergeSerdeError::FrameTooLarge(len)) if len == MAX_FRAME_LEN + 1
+    ));
+}
+
+#[tokio::test]
+async fn test_reconnect_with_backoff() {
+    let (peers_tx, mut peers) = mpsc::unbounded_channel();
+    let mut attempts = 0;
+    let open = move || {
+        attempts += 1;
+        // Refuse the first two attempts
+        ready(if attempts < 3 {
+            Err(io::Error::from(io::ErrorKind::ConnectionRefused))
+        } else {
+            let (io_a, io_b) = tokio::io::duplex(1024);
+            peers_tx.send(io_b).unwrap();
+            Ok(io_a)
+        })
+    };
+    let backoff = Backoff::new(Duration::from_millis(1), Duration::from_millis(4));
+    let (_a, run_a) = reconnect(
+        SyncSession::new(TypedDoc::new(&scene()).unwrap()),
+        open,
+        backoff,
+    );
+    tokio::spawn(run_a);
+
+    let (mut b, run_b) = connect(
+        SyncSession::<Scene>::new(TypedDoc::from_doc(Default::default())),
+        peers.recv().await.unwrap(),
+    );
+    tokio::spawn(run_b);
+    assert_eq!(b.next_update().await.unwrap().unwrap(), scene());
+}
+
+#[tokio::test]
+async fn test_reconnect_gives_up() {
+    let open = || {
+        ready(Err::<tokio::io::DuplexStream, _>(
+            io::ErrorKind::ConnectionRefused.into(),
+        ))
+    };
+    let backoff =
+        Backoff::new(Duration::from_millis(1), Duration::from_millis(1)).with_max_attempts(3);
+    let (_a, run_a) = reconnect(
+        SyncSession::new(TypedDoc::new(&scene()).unwrap()),
+        open,
+        backoff,
+    );
+    assert!(matches!(run_a.await, Err(AutomergeSerdeError::Io(_))));
+}
@@ -0,0 +1,186 @@
+use serde::{Deserialize, Serialize};
+use serde_automerge::{AutomergeGetExtension, ObjId, ReadDoc, TypedDoc};
+
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+struct Float3 {
+    x: i32,
+    y: i32,
+    z: i32,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+struct Scene {
+    position: Float3,
+    names: Vec<String>,
+    label: Option<String>,
+}
+
+fn scene() -> Scene {
+    Scene {
+        position: Float3 { x: 1, y: 2, z: 3 },
+        names: vec!["a".to_owned(), "b".to_owned(), "c".to_owned()],
+        label: None,
+    }
+}
+
+#[test]
+fn test_read_modify_roundtrip() {
+    let mut doc = TypedDoc::new(&scene()).unwrap();
+    assert_eq!(doc.read().unwrap(), scene());
+
+    let heads = doc.get_heads();
+    doc.modify(|s| {
+        s.position.x = 6;
+        s.names.truncate(1);
+    })
+    .unwrap();
+
+    let mut expected = scene();
+    expected.position.x = 6;
+    expected.names.truncate(1);
+    assert_eq!(doc.read().unwrap(), expected);
+    assert_eq!(
+        doc.doc()
+            .length(doc.doc().get(ObjId::Root, "names").unwrap().unwrap().1),
+        1
+    );
+
+    // Unchanged objects are reused rather than replaced
+    let position_before = doc
+        .doc()
+        .get_at(ObjId::Root, "position", &heads)
+        .unwrap()
+        .unwrap()
+        .1;
+    let position_after = doc.doc().get(ObjId::Root, "position").unwrap().unwrap().1;
+    assert_eq!(position_before, position_after);
+
+    let loaded = TypedDoc::<Scene>::load(&doc.save()).unwrap();
+    assert_eq!(loaded.read().unwrap(), expected);
+    let position: Option<Float3> = loaded.doc().get_value(ObjId::Root, "position").unwrap();
+    assert_eq!(position, Some(expected.position));
+}
+
+#[test]
+fn test_concurrent_modifications_merge() {
+    let mut doc = TypedDoc::new(&scene()).unwrap();
+    let mut fork = doc.fork();
+
+    doc.modify(|s| s.position.x = 10).unwrap();
+    fork.modify(|s| {
+        s.position.z = 30;
+        s.label = Some("fork".to_owned());
+    })
+    .unwrap();
+
+    doc.merge(&mut fork).unwrap();
+    let merged = doc.read().unwrap();
+    assert_eq!(merged.position, Float3 { x: 10, y: 2, z: 30 });
+    assert_eq!(merged.label.as_deref(), Some("fork"));
+}
+
+#[test]
+fn test_failed_write_keeps_pending_ops() {
+    use serde_automerge::{transaction::Transactable, ScalarValue};
+    use std::collections::BTreeMap;
+
+    // Maps with non-string keys can't be serialized
+    let mut doc = TypedDoc::new(&BTreeMap::<(u8, u8), u8>::new()).unwrap();
+    doc.doc_mut().put(ObjId::Root, "note", "pending").unwrap();
+    assert!(doc.write(&BTreeMap::from([((1, 2), 3)])).is_err());
+
+    let note = doc.doc().get(ObjId::Root, "note").unwrap().unwrap().0;
+    assert_eq!(note.to_scalar(), Some(&ScalarValue::from("pending")));
+}
+
+#[test]
+fn test_text_backed_strings_keep_concurrent_edits() {
+    use serde_automerge::{transaction::Transactable, AutoCommit, ObjType, Value};
+
+    #[derive(Debug, Deserialize, Serialize, PartialEq, Eq)]
+    struct Note {
+        title: String,
+        n: i64,
+    }
+
+    // Written the way JavaScript clients store strings
+    let mut base = AutoCommit::new();
+    let title = base
+        .put_object(ObjId::Root, "title", ObjType::Text)
+        .unwrap();
+    base.splice_text(&title, 0, 0, "hello").unwrap();
+    base.put(ObjId::Root, "n", 1).unwrap();
+    let mut doc = TypedDoc::<Note>::from_doc(base);
+    let mut peer = doc.fork();
+
+    doc.modify(|v| v.n = 2).unwrap();
+    let (value, id) = doc.doc().get(ObjId::Root, "title").unwrap().unwrap();
+    assert_eq!((value, &id), (Value::Object(ObjType::Text), &title));
+
+    peer.doc_mut().splice_text(&title, 5, 0, " world").unwrap();
+    doc.merge(&mut peer).unwrap();
+    assert_eq!(
+        doc.read().unwrap(),
+        Note {
+            title: "hello world".to_owned(),
+            n: 2,
+        }
+    );
+
+    // A changed string updates the text instead of replacing it
+    doc.modify(|v| v.title = "hello there world".to_owned())
+        .unwrap();
+    let (value, id) = doc.doc().get(ObjId::Root, "title").unwrap().unwrap();
+    assert_eq!((value, &id), (Value::Object(ObjType::Text), &title));
+    assert_eq!(doc.doc().text(&title).unwrap(), "hello there world");
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+struct Item {
+    name: String,
+    count: i64,
+}
+
+fn item(name: &str, count: i64) -> Item {
+    Item {
+        name: name.to_owned(),
+        count,
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+struct Items {
+    items: Vec<Item>,
+}
+
+#[test]
+fn test_list_insert_keeps_concurrent_element_edits() {
+    let mut doc = TypedDoc::new(&Items {
+        items: vec![item("a", 1), item("b", 1)],
+    })
+    .unwrap();
+    let mut peer = doc.fork();
+
+    peer.modify(|v| v.items[1].count = 5).unwrap();
+    doc.modify(|v| v.items.insert(0, item("z", 1))).unwrap();
+    doc.merge(&mut peer).unwrap();
+    assert_eq!(
+        doc.read().unwrap().items,
+        [item("z", 1), item("a", 1), item("b", 5)]
+    );
+
+    // Removing and changing elements keeps the others in place as well
+    let mut peer = doc.fork();
+    peer.modify(|v| {
+        v.items[1].name = "A".to_owned();
+        v.items[2].name = "c".to_owned();
+    })
+    .unwrap();
+    doc.modify(|v| {
+        v.items.remove(0);
+        v.items[0].count = 2;
+    })
+    .unwrap();
+    doc.merge(&mut peer).unwrap();
+    assert_eq!(doc.read().unwrap().items, [item("A", 2), item("c", 5)]);
+}
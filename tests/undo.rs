@@ -0,0 +1,180 @@
+use serde::{Deserialize, Serialize};
+use serde_automerge::{
+    marks::{ExpandMark, Mark},
+    transaction::Transactable,
+    AutoCommit, AutomergeError, ObjType, ReadDoc, ScalarValue, TypedDoc, UndoManager, Value, ROOT,
+};
+
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+struct Doc {
+    title: String,
+    count: i64,
+    items: Vec<String>,
+}
+
+fn value() -> Doc {
+    Doc {
+        title: "a".to_owned(),
+        count: 1,
+        items: vec!["x".to_owned()],
+    }
+}
+
+fn push_y(doc: &mut AutoCommit) -> Result<(), AutomergeError> {
+    let (_, items) = doc.get(ROOT, "items")?.unwrap();
+    doc.insert(&items, doc.length(&items), "y")
+}
+
+#[test]
+fn test_undo_redo() {
+    let mut doc = TypedDoc::new(&value()).unwrap();
+    let mut undo = UndoManager::new();
+    assert!(!undo.can_undo());
+
+    undo.transact(doc.doc_mut(), |d| d.put(ROOT, "title", "b"))
+        .unwrap();
+    undo.transact(doc.doc_mut(), push_y).unwrap();
+    assert_eq!(doc.read().unwrap().items, vec!["x", "y"]);
+
+    assert!(undo.undo(doc.doc_mut()).unwrap());
+    assert_eq!(doc.read().unwrap().items, vec!["x"]);
+    assert!(undo.undo(doc.doc_mut()).unwrap());
+    assert_eq!(doc.read().unwrap(), value());
+    assert!(!undo.undo(doc.doc_mut()).unwrap());
+
+    assert!(undo.redo(doc.doc_mut()).unwrap());
+    assert_eq!(doc.read().unwrap().title, "b");
+    assert!(undo.can_redo());
+}
+
+#[test]
+fn test_undo_keeps_concurrent_changes() {
+    let mut doc = TypedDoc::new(&value()).unwrap();
+    let mut undo = UndoManager::new();
+    let mut other = doc.fork();
+
+    let before = doc.get_heads();
+    doc.modify(|v| v.title = "mine".to_owned()).unwrap();
+    let after = doc.get_heads();
+    undo.record(doc.doc_mut().document(), &before, &after)
+        .unwrap();
+
+    other.modify(|v| v.count = 5).unwrap();
+    doc.merge(&mut other).unwrap();
+
+    undo.undo(doc.doc_mut()).unwrap();
+    assert_eq!(
+        doc.read().unwrap(),
+        Doc {
+            count: 5,
+            ..value()
+        }
+    );
+}
+
+#[test]
+fn test_undo_text_keeps_text_object() {
+    let mut doc = AutoCommit::new();
+    let text = doc.put_object(ROOT, "body", ObjType::Text).unwrap();
+    doc.splice_text(&text, 0, 0, "hello world").unwrap();
+    doc.mark(
+        &text,
+        Mark::new("bold".to_owned(), true, 0, 5),
+        ExpandMark::None,
+    )
+    .unwrap();
+    let mut undo = UndoManager::new();
+
+    undo.transact(&mut doc, |d| {
+        d.splice_text(&text, 0, 6, "")?;
+        d.splice_text(&text, 5, 0, "!")?;
+        Ok::<_, AutomergeError>(())
+    })
+    .unwrap();
+    assert_eq!(doc.text(&text).unwrap(), "world!");
+
+    undo.undo(&mut doc).unwrap();
+    let (value, id) = doc.get(ROOT, "body").unwrap().unwrap();
+    assert_eq!((value, &id), (Value::Object(ObjType::Text), &text));
+    assert_eq!(doc.text(&text).unwrap(), "hello world");
+    let marks = doc.marks(&text).unwrap();
+    assert_eq!(marks.len(), 1);
+    assert_eq!(
+        (marks[0].name(), marks[0].start, marks[0].end),
+        ("bold", 0, 5)
+    );
+
+    undo.redo(&mut doc).unwrap();
+    assert_eq!(doc.text(&text).unwrap(), "world!");
+}
+
+#[test]
+fn test_undo_keeps_concurrent_list_inserts() {
+    let mut doc = TypedDoc::new(&value()).unwrap();
+    let mut undo = UndoManager::new();
+    let mut other = doc.fork();
+
+    undo.transact(doc.doc_mut(), push_y).unwrap();
+    other.modify(|v| v.items.insert(0, "w".to_owned())).unwrap();
+    doc.merge(&mut other).unwrap();
+    assert_eq!(doc.read().unwrap().items, vec!["w", "x", "y"]);
+
+    undo.undo(doc.doc_mut()).unwrap();
+    assert_eq!(doc.read().unwrap().items, vec!["w", "x"]);
+}
+
+#[test]
+fn test_undo_restores_counter_and_deleted_elements() {
+    let mut doc = AutoCommit::new();
+    let list = doc.put_object(ROOT, "list", ObjType::List).unwrap();
+    doc.splice(&list, 0, 0, ["a".into(), "b".into(), "c".into()])
+        .unwrap();
+    doc.put(ROOT, "count", ScalarValue::counter(1)).unwrap();
+    let mut undo = UndoManager::new();
+
+    undo.transact(&mut doc, |d| {
+        d.delete(&list, 1)?;
+        d.put(ROOT, "count", 7)?;
+        Ok::<_, AutomergeError>(())
+    })
+    .unwrap();
+    undo.undo(&mut doc).unwrap();
+
+    let items = (0..doc.length(&list))
+        .map(|i| {
+            doc.get(&list, i)
+                .unwrap()
+                .unwrap()
+                .0
+                .to_str()
+                .unwrap()
+                .to_owned()
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(items, ["a", "b", "c"]);
+    assert!(matches!(
+        doc.get(ROOT, "count").unwrap(),
+        Some((Value::Scalar(s), _)) if matches!(*s, ScalarValue::Counter(_))
+    ));
+    doc.increment(ROOT, "count", 1).unwrap();
+}
+
+#[test]
+fn test_failed_undo_keeps_the_step() {
+    let mut doc = AutoCommit::new();
+    let mut other = doc.fork();
+    let list = other.put_object(ROOT, "list", ObjType::List).unwrap();
+    let mut undo = UndoManager::new();
+    undo.transact(&mut other, |d| d.insert(&list, 0, "y"))
+        .unwrap();
+
+    // The list does not exist in `doc` yet
+    assert!(undo.undo(&mut doc).is_err());
+    assert!(undo.can_undo());
+    assert!(!undo.can_redo());
+
+    doc.merge(&mut other).unwrap();
+    assert!(undo.undo(&mut doc).unwrap());
+    assert_eq!(doc.length(&list), 0);
+    assert!(undo.can_redo());
+}
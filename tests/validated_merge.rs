@@ -0,0 +1,103 @@
+use serde::{Deserialize, Serialize};
+use serde_automerge::{
+    merge::{Rejection, ValidatedMerge},
+    transaction::Transactable,
+    AutomergeSerdeError, ObjType, Prop, TypedDoc, ROOT,
+};
+
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+struct Settings {
+    theme: String,
+    size: i64,
+}
+
+fn settings() -> Settings {
+    Settings {
+        theme: "light".to_owned(),
+        size: 12,
+    }
+}
+
+#[test]
+fn test_rejects_schema_violation() {
+    let mut doc = TypedDoc::new(&settings()).unwrap();
+    let mut peer = doc.fork();
+    // A buggy peer turns the int into a map
+    peer.doc_mut()
+        .put_object(ROOT, "size", ObjType::Map)
+        .unwrap();
+    peer.doc_mut().commit();
+    let heads = doc.get_heads();
+
+    let merge = ValidatedMerge::new();
+    match merge.merge(&mut doc, &mut peer).unwrap_err() {
+        AutomergeSerdeError::Rejected(rejection) => match *rejection {
+            Rejection::Schema { changes, .. } => {
+                assert_eq!(changes.len(), 1);
+                assert_eq!(changes[0].path, vec![Prop::Map("size".to_owned())]);
+            }
+            other => panic!("expected a schema rejection, got {other:?}"),
+        },
+        other => panic!("expected a rejection, got {other:?}"),
+    }
+    assert_eq!(doc.get_heads(), heads);
+    assert_eq!(doc.read().unwrap(), settings());
+}
+
+#[test]
+fn test_validators() {
+    let merge = ValidatedMerge::new().with_validator(|s: &Settings| {
+        if s.size > 0 {
+            Ok(())
+        } else {
+            Err(format!("size must be positive, got {}", s.size))
+        }
+    });
+
+    let mut doc = TypedDoc::new(&settings()).unwrap();
+    let mut peer = doc.fork();
+    peer.modify(|s| s.size = -1).unwrap();
+    let rejection = merge
+        .load_incremental(&mut doc, &peer.save_incremental())
+        .unwrap_err();
+    assert_eq!(
+        rejection.to_string(),
+        "the merged value is invalid: size must be positive, got -1"
+    );
+    assert_eq!(doc.read().unwrap(), settings());
+
+    peer.modify(|s| s.size = 20).unwrap();
+    let merged = merge.merge(&mut doc, &mut peer).unwrap();
+    assert_eq!(merged.size, 20);
+    assert_eq!(doc.read().unwrap(), merged);
+}
+
+#[test]
+fn test_merges_edits_behind_concurrent_inserts() {
+    #[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+    struct Playlist {
+        songs: Vec<Settings>,
+    }
+    let song = |theme: &str, size| Settings {
+        theme: theme.to_owned(),
+        size,
+    };
+
+    let mut doc = TypedDoc::new(&Playlist {
+        songs: vec![song("a", 1), song("b", 1)],
+    })
+    .unwrap();
+    let mut peer = doc.fork();
+    doc.modify(|p| p.songs.insert(0, song("z", 1))).unwrap();
+    peer.modify(|p| p.songs[1].size = 5).unwrap();
+
+    let merge = ValidatedMerge::new().with_validator(|p: &Playlist| {
+        match p.songs.iter().all(|s| s.size > 0) {
+            true => Ok(()),
+            false => Err("sizes must be positive".to_owned()),
+        }
+    });
+    let merged = merge.merge(&mut doc, &mut peer).unwrap();
+    assert_eq!(merged.songs, [song("z", 1), song("a", 1), song("b", 5)]);
+    assert_eq!(doc.read().unwrap(), merged);
+}